@@ -0,0 +1,178 @@
+//! Copying and pasting parts of the [`Treeize`].
+
+use std::collections::HashMap;
+
+use egui::{Pos2, Rect};
+
+use crate::{InPinId, Node, NodeId, OutPinId, Treeize};
+
+/// Wire between two nodes of a [`TreeizeFragment`].
+///
+/// Nodes are referenced by their index in the fragment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct FragmentWire {
+  from: usize,
+  output: usize,
+  to: usize,
+  input: usize,
+}
+
+/// Detached part of a [`Treeize`] - nodes and wires among them.
+///
+/// Created with [`Treeize::copy_nodes`] and inserted back with [`Treeize::paste_fragment`].
+/// Node positions are relative to the top-left corner of the fragment.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeizeFragment<T> {
+  nodes: Vec<Node<T>>,
+  wires: Vec<FragmentWire>,
+}
+
+impl<T> TreeizeFragment<T> {
+  /// Returns number of nodes in the fragment.
+  #[must_use]
+  pub const fn len(&self) -> usize {
+    self.nodes.len()
+  }
+
+  /// Returns true if the fragment has no nodes.
+  #[must_use]
+  pub const fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
+  }
+
+  /// Iterates over nodes of the fragment.
+  pub fn nodes(&self) -> impl Iterator<Item = &Node<T>> + '_ {
+    self.nodes.iter()
+  }
+
+  /// Returns indices of nodes that have no incoming wires within the fragment.
+  #[cfg(feature = "serde")]
+  pub(crate) fn roots(&self) -> Vec<usize> {
+    (0..self.nodes.len()).filter(|&idx| !self.wires.iter().any(|wire| wire.to == idx)).collect()
+  }
+
+  /// Serializes the fragment into text suitable for the system clipboard.
+  ///
+  /// # Errors
+  ///
+  /// Returns error if node value fails to serialize.
+  #[cfg(feature = "serde")]
+  pub fn to_clipboard_text(&self) -> Result<String, serde_json::Error>
+  where
+    T: serde::Serialize,
+  {
+    serde_json::to_string(self)
+  }
+
+  /// Deserializes the fragment from the text produced by [`TreeizeFragment::to_clipboard_text`].
+  ///
+  /// # Errors
+  ///
+  /// Returns error if the text is not a serialized fragment.
+  #[cfg(feature = "serde")]
+  pub fn from_clipboard_text(text: &str) -> Result<Self, serde_json::Error>
+  where
+    T: serde::de::DeserializeOwned,
+  {
+    serde_json::from_str(text)
+  }
+}
+
+impl<T> Treeize<T> {
+  /// Copies nodes and wires among them into a [`TreeizeFragment`].
+  ///
+  /// Wires to nodes outside of the list are not copied.
+  /// Missing nodes are ignored.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use egui_treeize::{InPinId, OutPinId, Treeize};
+  /// let mut treeize = Treeize::new();
+  /// let a = treeize.insert_node(egui::pos2(0.0, 0.0), "a");
+  /// let b = treeize.insert_node(egui::pos2(0.0, 100.0), "b");
+  /// treeize.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+  ///
+  /// let fragment = treeize.copy_nodes(&[a, b]);
+  /// let pasted = treeize.paste_fragment(fragment, egui::pos2(200.0, 0.0));
+  ///
+  /// assert_eq!(pasted.len(), 2);
+  /// assert_eq!(treeize.wires().count(), 2);
+  /// ```
+  #[must_use]
+  pub fn copy_nodes(&self, nodes: &[NodeId]) -> TreeizeFragment<T>
+  where
+    T: Clone,
+  {
+    // Index of each copied node in the fragment.
+    let mut index = HashMap::with_capacity(nodes.len());
+    let mut ids = Vec::with_capacity(nodes.len());
+    for &node in nodes {
+      if self.nodes.contains(node.0) && !index.contains_key(&node) {
+        index.insert(node, ids.len());
+        ids.push(node);
+      }
+    }
+
+    let mut bb = Rect::NOTHING;
+    for node in &ids {
+      bb.extend_with(self.nodes[node.0].pos);
+    }
+
+    let nodes = ids
+      .iter()
+      .map(|node| {
+        let node = &self.nodes[node.0];
+        Node { value: node.value.clone(), pos: (node.pos - bb.min).to_pos2(), open: node.open }
+      })
+      .collect();
+
+    let wires = self
+      .wires
+      .iter()
+      .filter_map(|wire| {
+        Some(FragmentWire {
+          from: *index.get(&wire.out_pin.node)?,
+          output: wire.out_pin.output,
+          to: *index.get(&wire.in_pin.node)?,
+          input: wire.in_pin.input,
+        })
+      })
+      .collect();
+
+    TreeizeFragment { nodes, wires }
+  }
+
+  /// Inserts nodes and wires of the fragment.
+  ///
+  /// Top-left corner of the fragment is placed at `pos`.
+  /// Returns ids of inserted nodes in the order they were copied.
+  pub fn paste_fragment(&mut self, fragment: TreeizeFragment<T>, pos: Pos2) -> Vec<NodeId> {
+    let ids = fragment
+      .nodes
+      .into_iter()
+      .map(|node| {
+        NodeId(self.nodes.insert(Node {
+          value: node.value,
+          pos: pos + node.pos.to_vec2(),
+          open: node.open,
+        }))
+      })
+      .collect::<Vec<_>>();
+
+    for wire in fragment.wires {
+      let (Some(&from), Some(&to)) = (ids.get(wire.from), ids.get(wire.to)) else {
+        continue;
+      };
+
+      self.connect(
+        OutPinId { node: from, output: wire.output },
+        InPinId { node: to, input: wire.input },
+      );
+    }
+
+    ids
+  }
+}
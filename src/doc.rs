@@ -0,0 +1,334 @@
+//! Viewer of structured documents.
+//!
+//! Converts a [`serde_json::Value`] or any serializable value, e.g. a parsed TOML file,
+//! into a [`Treeize<DocNode>`] and shows it with [`DocViewer`].
+//! Objects and arrays become inner nodes, scalars become leaves.
+//! Each container is connected to its entries with wires from output `0` to input `0`.
+
+use std::collections::VecDeque;
+
+use egui::{Color32, Pos2, Ui};
+use serde_json::Value;
+
+use crate::{
+  InPin, InPinId, NodeId, OutPin, OutPinId, Treeize,
+  ui::{PinInfo, TreeizePin, TreeizeViewer},
+};
+
+/// Type of the document value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum DocValueKind {
+  /// Null value.
+  Null,
+
+  /// Boolean value.
+  Bool,
+
+  /// Number value.
+  Number,
+
+  /// String value.
+  String,
+
+  /// Array of values.
+  Array,
+
+  /// Object with keyed values.
+  Object,
+}
+
+impl DocValueKind {
+  /// Returns kind of the value.
+  #[must_use]
+  pub const fn of(value: &Value) -> Self {
+    match value {
+      Value::Null => DocValueKind::Null,
+      Value::Bool(_) => DocValueKind::Bool,
+      Value::Number(_) => DocValueKind::Number,
+      Value::String(_) => DocValueKind::String,
+      Value::Array(_) => DocValueKind::Array,
+      Value::Object(_) => DocValueKind::Object,
+    }
+  }
+
+  /// Returns true for arrays and objects.
+  #[must_use]
+  pub const fn is_container(self) -> bool {
+    matches!(self, DocValueKind::Array | DocValueKind::Object)
+  }
+
+  /// Returns color used for pins of this kind.
+  #[must_use]
+  pub const fn color(self) -> Color32 {
+    match self {
+      DocValueKind::Null => Color32::GRAY,
+      DocValueKind::Bool => Color32::from_rgb(0xE0, 0x90, 0x40),
+      DocValueKind::Number => Color32::from_rgb(0x50, 0xA0, 0xE0),
+      DocValueKind::String => Color32::from_rgb(0x70, 0xC0, 0x60),
+      DocValueKind::Array => Color32::from_rgb(0xB0, 0x70, 0xD0),
+      DocValueKind::Object => Color32::from_rgb(0xD0, 0xB0, 0x40),
+    }
+  }
+}
+
+/// Node of a document imported by [`import_value`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct DocNode {
+  /// Key of the value in its parent object, or `[index]` in its parent array.
+  /// `None` for the document root.
+  pub key: Option<String>,
+
+  /// Kind of the value.
+  pub kind: DocValueKind,
+
+  /// Text of a scalar value. Empty for arrays and objects.
+  pub value: String,
+
+  /// Number of entries of an array or object. Zero for scalars.
+  pub len: usize,
+
+  /// [JSON pointer](https://www.rfc-editor.org/rfc/rfc6901) to the value in the document.
+  /// Empty for the document root.
+  pub pointer: String,
+
+  /// Whether entries of the array or object are inserted.
+  /// Always true for scalars.
+  pub loaded: bool,
+}
+
+impl DocNode {
+  fn new(key: Option<String>, pointer: String, value: &Value) -> Self {
+    let kind = DocValueKind::of(value);
+    let (text, len) = match value {
+      Value::Array(array) => (String::new(), array.len()),
+      Value::Object(object) => (String::new(), object.len()),
+      Value::String(string) => (format!("{string:?}"), 0),
+      scalar => (scalar.to_string(), 0),
+    };
+
+    DocNode { key, kind, value: text, len, pointer, loaded: true }
+  }
+}
+
+/// Configuration for [`import_value`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocImportConfig {
+  /// Arrays with more entries than this are inserted collapsed without their entries.
+  /// They can be loaded later with [`expand_value`].
+  pub collapse_len: usize,
+}
+
+impl Default for DocImportConfig {
+  fn default() -> Self {
+    DocImportConfig { collapse_len: 16 }
+  }
+}
+
+/// Converts the document into a new [`Treeize`].
+///
+/// Returns the treeize and the id of the root node.
+/// Arrays longer than [`DocImportConfig::collapse_len`] are inserted collapsed
+/// and without entries.
+/// All nodes are placed at origin, use [`layout`](crate::layout) to arrange them.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::doc::{DocImportConfig, import_value};
+/// let value = serde_json::json!({ "name": "treeize", "tags": ["egui", "tree"] });
+/// let (treeize, root) = import_value(&value, &DocImportConfig::default());
+///
+/// assert_eq!(treeize[root].len, 2);
+/// assert_eq!(treeize.nodes().count(), 5);
+/// ```
+#[must_use]
+pub fn import_value(value: &Value, config: &DocImportConfig) -> (Treeize<DocNode>, NodeId) {
+  let mut treeize = Treeize::new();
+  let root = insert_value(&mut treeize, None, String::new(), value, config);
+  load_entries(&mut treeize, root, value, config);
+
+  (treeize, root)
+}
+
+/// Loads entries of an array that was inserted without them.
+///
+/// `document` is the value passed to [`import_value`].
+/// Opens the node and loads its entries, leaving long nested arrays collapsed again.
+/// Returns ids of inserted nodes. Does nothing for already loaded nodes.
+///
+/// # Panics
+///
+/// Panics if the node does not exist.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::doc::{DocImportConfig, expand_value, import_value};
+/// let document = serde_json::json!({ "items": (0..100).collect::<Vec<_>>() });
+/// let (mut treeize, root) = import_value(&document, &DocImportConfig::default());
+///
+/// // The long array is collapsed and adds no child nodes.
+/// assert_eq!(treeize.nodes().count(), 2);
+/// let (items, node) = treeize.nodes_ids_data().find(|(id, _)| *id != root).unwrap();
+/// assert!(!node.open && !node.value.loaded);
+/// assert_eq!(treeize.wires().count(), 1);
+///
+/// let inserted = expand_value(&mut treeize, items, &document, &DocImportConfig::default());
+/// assert_eq!(inserted.len(), 100);
+/// assert_eq!(treeize[inserted[42]].pointer, "/items/42");
+/// assert!(treeize[items].loaded);
+///
+/// // Loaded nodes are not loaded again.
+/// assert!(expand_value(&mut treeize, items, &document, &DocImportConfig::default()).is_empty());
+/// ```
+pub fn expand_value(
+  treeize: &mut Treeize<DocNode>,
+  node: NodeId,
+  document: &Value,
+  config: &DocImportConfig,
+) -> Vec<NodeId> {
+  if treeize[node].loaded {
+    return Vec::new();
+  }
+  let Some(value) = document.pointer(&treeize[node].pointer) else {
+    return Vec::new();
+  };
+
+  treeize.open_node(node, true);
+  load_entries(treeize, node, value, config)
+}
+
+/// Converts any serializable value into a new [`Treeize`].
+///
+/// Serializes the value into [`serde_json::Value`] and calls [`import_value`].
+///
+/// # Errors
+///
+/// Returns error if the value fails to serialize or has non-string map keys.
+pub fn import_serialize<S>(
+  value: &S,
+  config: &DocImportConfig,
+) -> Result<(Treeize<DocNode>, NodeId), serde_json::Error>
+where
+  S: serde::Serialize + ?Sized,
+{
+  let value = serde_json::to_value(value)?;
+  Ok(import_value(&value, config))
+}
+
+fn insert_value(
+  treeize: &mut Treeize<DocNode>,
+  key: Option<String>,
+  pointer: String,
+  value: &Value,
+  config: &DocImportConfig,
+) -> NodeId {
+  let mut node = DocNode::new(key, pointer, value);
+  if node.kind == DocValueKind::Array && node.len > config.collapse_len {
+    node.loaded = false;
+    treeize.insert_node_collapsed(Pos2::ZERO, node)
+  } else {
+    treeize.insert_node(Pos2::ZERO, node)
+  }
+}
+
+/// Inserts entries of the container node breadth-first, down to collapsed arrays.
+/// Returns ids of inserted nodes.
+fn load_entries(
+  treeize: &mut Treeize<DocNode>,
+  node: NodeId,
+  value: &Value,
+  config: &DocImportConfig,
+) -> Vec<NodeId> {
+  let mut inserted = Vec::new();
+  treeize[node].loaded = true;
+
+  let mut queue = VecDeque::from([(node, value)]);
+  while let Some((parent, value)) = queue.pop_front() {
+    let entries: Box<dyn Iterator<Item = (String, String, &Value)>> = match value {
+      Value::Array(array) => Box::new(
+        array.iter().enumerate().map(|(idx, value)| (format!("[{idx}]"), idx.to_string(), value)),
+      ),
+      Value::Object(object) => Box::new(
+        object
+          .iter()
+          .map(|(key, value)| (key.clone(), key.replace('~', "~0").replace('/', "~1"), value)),
+      ),
+      _ => continue,
+    };
+
+    for (key, token, value) in entries {
+      let pointer = format!("{}/{token}", treeize[parent].pointer);
+      let child = insert_value(treeize, Some(key), pointer, value, config);
+      treeize.connect(OutPinId { node: parent, output: 0 }, InPinId { node: child, input: 0 });
+      inserted.push(child);
+
+      if treeize[child].loaded {
+        queue.push_back((child, value));
+      }
+    }
+  }
+
+  inserted
+}
+
+/// Read-only [`TreeizeViewer`] for documents imported by [`import_value`].
+///
+/// Shows keys in headers and scalar values in bodies.
+/// Pins are colored by [`DocValueKind::color`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DocViewer;
+
+impl TreeizeViewer<DocNode> for DocViewer {
+  fn title(&mut self, node: &DocNode) -> String {
+    let key = node.key.as_deref().unwrap_or("$");
+    match node.kind {
+      DocValueKind::Array => format!("{key} [{}]", node.len),
+      DocValueKind::Object => format!("{key} {{{}}}", node.len),
+      _ => key.to_owned(),
+    }
+  }
+
+  fn has_input(&mut self, node: &DocNode) -> bool {
+    node.key.is_some()
+  }
+
+  fn has_output(&mut self, node: &DocNode) -> bool {
+    node.kind.is_container()
+  }
+
+  fn show_input(
+    &mut self,
+    pin: &InPin,
+    _ui: &mut Ui,
+    treeize: &mut Treeize<DocNode>,
+  ) -> impl TreeizePin + 'static {
+    PinInfo::circle().with_fill(treeize[pin.id.node].kind.color())
+  }
+
+  fn show_output(
+    &mut self,
+    pin: &OutPin,
+    _ui: &mut Ui,
+    treeize: &mut Treeize<DocNode>,
+  ) -> impl TreeizePin + 'static {
+    PinInfo::circle().with_fill(treeize[pin.id.node].kind.color())
+  }
+
+  fn has_body(&mut self, node: &DocNode) -> bool {
+    !node.kind.is_container()
+  }
+
+  fn show_body(&mut self, node: NodeId, ui: &mut Ui, treeize: &mut Treeize<DocNode>) {
+    let node = &treeize[node];
+    ui.colored_label(node.kind.color(), &node.value);
+  }
+
+  fn connect(&mut self, _from: &OutPin, _to: &InPin, _treeize: &mut Treeize<DocNode>) {}
+
+  fn disconnect(&mut self, _from: &OutPin, _to: &InPin, _treeize: &mut Treeize<DocNode>) {}
+
+  fn drop_outputs(&mut self, _pin: &OutPin, _treeize: &mut Treeize<DocNode>) {}
+
+  fn drop_inputs(&mut self, _pin: &InPin, _treeize: &mut Treeize<DocNode>) {}
+}
@@ -0,0 +1,374 @@
+//! Importer of local directory trees.
+//!
+//! Walks a directory into a [`Treeize<FsEntry>`] to use the widget as a file browser.
+//! Each directory is connected to its entries with wires from output `0` to input `0`.
+
+use std::{
+  collections::{HashMap, HashSet, VecDeque},
+  fs, io,
+  path::{Path, PathBuf},
+};
+
+use egui::Pos2;
+use glob::Pattern;
+
+use crate::{InPinId, NodeId, OutPinId, Treeize};
+
+/// Kind of the file system entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FsEntryKind {
+  /// Regular file.
+  File,
+
+  /// Directory.
+  Directory,
+
+  /// Symbolic link. Links are never followed.
+  Symlink,
+}
+
+/// File system entry imported by [`import_dir`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsEntry {
+  /// File name of the entry.
+  pub name: String,
+
+  /// Full path of the entry.
+  pub path: PathBuf,
+
+  /// Kind of the entry.
+  pub kind: FsEntryKind,
+
+  /// Size of the entry itself in bytes.
+  pub size: u64,
+
+  /// Size of the entry and all loaded descendants in bytes.
+  /// Updated by [`roll_up_sizes`].
+  pub total_size: u64,
+
+  /// Whether entries of the directory are loaded.
+  /// Always true for files and symlinks.
+  pub loaded: bool,
+}
+
+impl FsEntry {
+  fn new(path: PathBuf, metadata: &fs::Metadata) -> Self {
+    let kind = if metadata.is_symlink() {
+      FsEntryKind::Symlink
+    } else if metadata.is_dir() {
+      FsEntryKind::Directory
+    } else {
+      FsEntryKind::File
+    };
+
+    let name = path.file_name().map_or_else(
+      || path.to_string_lossy().into_owned(),
+      |name| name.to_string_lossy().into_owned(),
+    );
+
+    let size = if kind == FsEntryKind::Directory { 0 } else { metadata.len() };
+
+    FsEntry { name, path, kind, size, total_size: size, loaded: kind != FsEntryKind::Directory }
+  }
+
+  /// Returns true if the entry is a directory.
+  #[must_use]
+  pub fn is_dir(&self) -> bool {
+    self.kind == FsEntryKind::Directory
+  }
+}
+
+/// Configuration for [`import_dir`] and [`expand_dir`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FsImportConfig {
+  /// Maximum depth of loaded directories.
+  ///
+  /// Directories deeper than this are inserted collapsed without entries
+  /// and can be loaded later with [`expand_dir`].
+  /// `None` loads the whole tree.
+  pub max_depth: Option<usize>,
+
+  /// Glob patterns of entries to skip, e.g. `"target"` or `"*.tmp"`.
+  /// Patterns are matched against both the file name and the full path.
+  pub ignore: Vec<String>,
+}
+
+/// Walks the directory into a new [`Treeize`].
+///
+/// Returns the treeize and the id of the node for `path`.
+/// Entries of a directory are ordered with directories first, then by name.
+/// Unreadable nested directories are left without entries.
+/// All nodes are placed at origin, use [`layout`](crate::layout) to arrange them.
+///
+/// # Errors
+///
+/// Returns error if `path` can't be read or an ignore pattern is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use std::fs;
+/// # use egui_treeize::fs::{FsImportConfig, import_dir};
+/// # let name = format!("egui-treeize-{}-{}", std::process::id(), line!());
+/// # let root = std::env::temp_dir().join(name);
+/// # let _ = fs::remove_dir_all(&root);
+/// # fs::create_dir_all(root.join("docs"))?;
+/// # fs::create_dir_all(root.join("src/lib/deep"))?;
+/// # fs::write(root.join("a.txt"), "hello")?;
+/// # fs::write(root.join("b.tmp"), "scratch")?;
+/// # fs::write(root.join("docs/guide.txt"), "welcome")?;
+/// # fs::write(root.join("docs/readme.md"), "ignored")?;
+/// # fs::write(root.join("src/lib/deep/x.txt"), "abc")?;
+/// // root/
+/// //   a.txt              5 bytes
+/// //   b.tmp              7 bytes
+/// //   docs/guide.txt     7 bytes
+/// //   docs/readme.md     7 bytes
+/// //   src/lib/deep/x.txt 3 bytes
+///
+/// let config = FsImportConfig {
+///   max_depth: Some(2),
+///   // Matched against the file name and against the full path.
+///   ignore: vec!["*.tmp".to_owned(), "**/docs/*.md".to_owned()],
+/// };
+/// let (treeize, node) = import_dir(&root, &config)?;
+///
+/// let names = treeize.nodes().map(|entry| entry.name.as_str()).collect::<Vec<_>>();
+/// assert!(names.contains(&"guide.txt"));
+/// assert!(!names.contains(&"b.tmp") && !names.contains(&"readme.md"));
+///
+/// // `lib` is past the maximum depth, it is collapsed and its entries are not loaded.
+/// let (_, entry) = treeize.nodes_ids_data().find(|(_, node)| node.value.name == "lib").unwrap();
+/// assert!(!entry.open && !entry.value.loaded);
+/// assert!(!names.contains(&"deep"));
+///
+/// // Only `a.txt` and `guide.txt` are loaded.
+/// assert_eq!(treeize[node].total_size, 5 + 7);
+///
+/// let invalid = FsImportConfig { ignore: vec!["[".to_owned()], ..Default::default() };
+/// let err = import_dir(&root, &invalid).unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+/// # fs::remove_dir_all(&root)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn import_dir(
+  path: impl AsRef<Path>,
+  config: &FsImportConfig,
+) -> io::Result<(Treeize<FsEntry>, NodeId)> {
+  let path = path.as_ref();
+  let ignore = compile_patterns(&config.ignore)?;
+
+  let metadata = fs::symlink_metadata(path)?;
+  let entry = FsEntry::new(path.to_path_buf(), &metadata);
+  // Report errors for the root directory itself.
+  if entry.is_dir() {
+    fs::read_dir(path)?;
+  }
+
+  let mut treeize = Treeize::new();
+  let root = insert_entry(&mut treeize, entry, 0, config.max_depth);
+
+  load_entries(&mut treeize, root, config.max_depth, &ignore);
+  roll_up_sizes(&mut treeize);
+
+  Ok((treeize, root))
+}
+
+/// Loads entries of a directory that was inserted without them.
+///
+/// Loads up to [`FsImportConfig::max_depth`] levels below the node, opens it
+/// and updates sizes with [`roll_up_sizes`].
+/// Returns ids of inserted nodes. Does nothing for already loaded entries.
+///
+/// # Errors
+///
+/// Returns error if the directory can't be read or an ignore pattern is invalid.
+///
+/// # Panics
+///
+/// Panics if the node does not exist.
+///
+/// # Examples
+///
+/// ```
+/// # use std::fs;
+/// # use egui_treeize::fs::{FsImportConfig, expand_dir, import_dir};
+/// # let name = format!("egui-treeize-{}-{}", std::process::id(), line!());
+/// # let root = std::env::temp_dir().join(name);
+/// # let _ = fs::remove_dir_all(&root);
+/// # fs::create_dir_all(root.join("docs"))?;
+/// # fs::create_dir_all(root.join("src/lib/deep"))?;
+/// # fs::write(root.join("a.txt"), "hello")?;
+/// # fs::write(root.join("b.tmp"), "scratch")?;
+/// # fs::write(root.join("docs/guide.txt"), "welcome")?;
+/// # fs::write(root.join("docs/readme.md"), "ignored")?;
+/// # fs::write(root.join("src/lib/deep/x.txt"), "abc")?;
+/// // root/
+/// //   a.txt              5 bytes
+/// //   b.tmp              7 bytes
+/// //   docs/guide.txt     7 bytes
+/// //   docs/readme.md     7 bytes
+/// //   src/lib/deep/x.txt 3 bytes
+///
+/// let config = FsImportConfig { max_depth: Some(2), ..Default::default() };
+/// let (mut treeize, node) = import_dir(&root, &config)?;
+/// let lib = treeize.nodes_ids_data().find(|(_, node)| node.value.name == "lib").unwrap().0;
+///
+/// let inserted = expand_dir(&mut treeize, lib, &config)?;
+/// let names = inserted.iter().map(|&id| treeize[id].name.as_str()).collect::<Vec<_>>();
+/// assert_eq!(names, ["deep", "x.txt"]);
+/// assert!(treeize[lib].loaded);
+/// // Nothing is ignored, every file is loaded now.
+/// assert_eq!(treeize[node].total_size, 5 + 7 + 7 + 7 + 3);
+///
+/// // Loaded directories are not read again.
+/// assert!(expand_dir(&mut treeize, lib, &config)?.is_empty());
+/// assert_eq!(treeize.nodes().count(), 10);
+/// # fs::remove_dir_all(&root)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn expand_dir(
+  treeize: &mut Treeize<FsEntry>,
+  node: NodeId,
+  config: &FsImportConfig,
+) -> io::Result<Vec<NodeId>> {
+  let ignore = compile_patterns(&config.ignore)?;
+
+  let entry = &treeize[node];
+  if entry.loaded {
+    return Ok(Vec::new());
+  }
+  fs::read_dir(&entry.path)?;
+
+  // Expanded directory always gets at least its own entries.
+  let max_depth = config.max_depth.map(|max_depth| max_depth.max(1));
+
+  treeize.open_node(node, true);
+  let inserted = load_entries(treeize, node, max_depth, &ignore);
+  roll_up_sizes(treeize);
+
+  Ok(inserted)
+}
+
+/// Updates [`FsEntry::total_size`] of every entry to include all loaded descendants.
+pub fn roll_up_sizes(treeize: &mut Treeize<FsEntry>) {
+  let mut children: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+  let mut has_parent = HashSet::new();
+  for (out_pin, in_pin) in treeize.wires() {
+    children.entry(out_pin.node).or_default().push(in_pin.node);
+    has_parent.insert(in_pin.node);
+  }
+
+  let roots =
+    treeize.node_ids().map(|(id, _)| id).filter(|id| !has_parent.contains(id)).collect::<Vec<_>>();
+
+  let mut totals: HashMap<NodeId, u64> = HashMap::new();
+
+  // Iterative post-order traversal, so deep trees can't overflow the stack.
+  for root in roots {
+    let mut stack = vec![(root, false)];
+    while let Some((node, visited)) = stack.pop() {
+      if visited {
+        let total = children
+          .get(&node)
+          .into_iter()
+          .flatten()
+          .map(|child| totals.get(child).copied().unwrap_or(0))
+          .fold(treeize[node].size, u64::saturating_add);
+        totals.insert(node, total);
+        continue;
+      }
+
+      if totals.contains_key(&node) {
+        continue;
+      }
+
+      stack.push((node, true));
+      for &child in children.get(&node).into_iter().flatten() {
+        if !totals.contains_key(&child) {
+          stack.push((child, false));
+        }
+      }
+    }
+  }
+
+  for (node, total) in totals {
+    treeize[node].total_size = total;
+  }
+}
+
+fn compile_patterns(patterns: &[String]) -> io::Result<Vec<Pattern>> {
+  patterns
+    .iter()
+    .map(|pattern| {
+      Pattern::new(pattern).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    })
+    .collect()
+}
+
+fn is_ignored(ignore: &[Pattern], entry: &FsEntry) -> bool {
+  ignore.iter().any(|pattern| pattern.matches(&entry.name) || pattern.matches_path(&entry.path))
+}
+
+/// Inserts the entry, collapsed if its entries won't be loaded at this depth.
+fn insert_entry(
+  treeize: &mut Treeize<FsEntry>,
+  entry: FsEntry,
+  depth: usize,
+  max_depth: Option<usize>,
+) -> NodeId {
+  if entry.is_dir() && max_depth.is_some_and(|max_depth| depth >= max_depth) {
+    treeize.insert_node_collapsed(Pos2::ZERO, entry)
+  } else {
+    treeize.insert_node(Pos2::ZERO, entry)
+  }
+}
+
+/// Loads entries of the directory node breadth-first up to `max_depth` levels below it.
+/// Returns ids of inserted nodes.
+fn load_entries(
+  treeize: &mut Treeize<FsEntry>,
+  node: NodeId,
+  max_depth: Option<usize>,
+  ignore: &[Pattern],
+) -> Vec<NodeId> {
+  let mut inserted = Vec::new();
+  let mut queue = VecDeque::from([(node, 0)]);
+
+  while let Some((parent, depth)) = queue.pop_front() {
+    if max_depth.is_some_and(|max_depth| depth >= max_depth) {
+      continue;
+    }
+
+    let Ok(read_dir) = fs::read_dir(&treeize[parent].path) else {
+      continue;
+    };
+    treeize[parent].loaded = true;
+
+    let mut entries = read_dir
+      .filter_map(Result::ok)
+      .filter_map(|dir_entry| {
+        let metadata = dir_entry.path().symlink_metadata().ok()?;
+        Some(FsEntry::new(dir_entry.path(), &metadata))
+      })
+      .filter(|entry| !is_ignored(ignore, entry))
+      .collect::<Vec<_>>();
+
+    entries.sort_by(|a, b| b.is_dir().cmp(&a.is_dir()).then_with(|| a.name.cmp(&b.name)));
+
+    for entry in entries {
+      let is_dir = entry.is_dir();
+      let child = insert_entry(treeize, entry, depth + 1, max_depth);
+      treeize.connect(OutPinId { node: parent, output: 0 }, InPinId { node: child, input: 0 });
+      inserted.push(child);
+
+      if is_dir {
+        queue.push_back((child, depth + 1));
+      }
+    }
+  }
+
+  inserted
+}
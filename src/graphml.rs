@@ -0,0 +1,628 @@
+//! [GraphML](http://graphml.graphdrawing.org/) export and import.
+//!
+//! Allows exchanging [`Treeize`] graphs with tools like yEd and Gephi.
+//!
+//! Nodes are written with their position as `x`/`y` attributes and
+//! the title provided by [`TreeizeViewer::title`].
+//! Wires are written as edges with `sourceport`/`targetport` named after
+//! the pin index, e.g. `out0` and `in0`.
+
+use std::{
+  collections::HashMap,
+  fmt,
+  io::{self, BufRead, Write},
+};
+
+use egui::{Pos2, pos2};
+use quick_xml::{
+  Reader,
+  escape::escape,
+  events::{BytesStart, Event},
+};
+
+use crate::{InPinId, NodeId, OutPinId, Treeize, ui::TreeizeViewer};
+
+const KEY_X: &str = "x";
+const KEY_Y: &str = "y";
+const KEY_TITLE: &str = "title";
+const KEY_OPEN: &str = "open";
+
+/// Type of a GraphML attribute.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GraphMlType {
+  /// `boolean` attribute.
+  Boolean,
+
+  /// `int` attribute.
+  Int,
+
+  /// `long` attribute.
+  Long,
+
+  /// `float` attribute.
+  Float,
+
+  /// `double` attribute.
+  Double,
+
+  /// `string` attribute.
+  #[default]
+  String,
+}
+
+impl GraphMlType {
+  const fn as_str(self) -> &'static str {
+    match self {
+      GraphMlType::Boolean => "boolean",
+      GraphMlType::Int => "int",
+      GraphMlType::Long => "long",
+      GraphMlType::Float => "float",
+      GraphMlType::Double => "double",
+      GraphMlType::String => "string",
+    }
+  }
+}
+
+/// User-defined node attribute key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GraphMlKey {
+  /// Name of the attribute, written as `attr.name`.
+  ///
+  /// Must not clash with built-in `x`, `y`, `title` and `open` attributes.
+  pub name: String,
+
+  /// Type of the attribute, written as `attr.type`.
+  pub ty: GraphMlType,
+}
+
+impl GraphMlKey {
+  /// Creates new attribute key.
+  #[must_use]
+  pub fn new(name: impl Into<String>, ty: GraphMlType) -> Self {
+    GraphMlKey { name: name.into(), ty }
+  }
+}
+
+/// Node read from GraphML document.
+///
+/// Passed to the attribute-mapping callback of [`import_graphml`]
+/// to construct node value.
+#[derive(Clone, Debug)]
+pub struct GraphMlNode {
+  /// Node identifier in the document.
+  pub id: String,
+
+  /// Position read from `x` and `y` attributes.
+  /// Missing coordinates default to zero.
+  pub pos: Pos2,
+
+  /// Title read from `title` attribute.
+  pub title: Option<String>,
+
+  /// Open flag read from `open` attribute.
+  /// Nodes are open unless the attribute says otherwise.
+  pub open: bool,
+
+  /// All node attributes, including built-in ones, by `attr.name`.
+  pub attributes: HashMap<String, String>,
+}
+
+/// Error that may occur during GraphML export or import.
+#[derive(Debug)]
+pub enum GraphMlError {
+  /// I/O error.
+  Io(io::Error),
+
+  /// Malformed XML.
+  Xml(quick_xml::Error),
+
+  /// Well-formed XML that is not a valid GraphML graph.
+  Invalid(String),
+}
+
+impl fmt::Display for GraphMlError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      GraphMlError::Io(err) => write!(f, "I/O error: {err}"),
+      GraphMlError::Xml(err) => write!(f, "XML error: {err}"),
+      GraphMlError::Invalid(msg) => write!(f, "invalid GraphML: {msg}"),
+    }
+  }
+}
+
+impl std::error::Error for GraphMlError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      GraphMlError::Io(err) => Some(err),
+      GraphMlError::Xml(err) => Some(err),
+      GraphMlError::Invalid(_) => None,
+    }
+  }
+}
+
+impl From<io::Error> for GraphMlError {
+  fn from(err: io::Error) -> Self {
+    GraphMlError::Io(err)
+  }
+}
+
+impl From<quick_xml::Error> for GraphMlError {
+  fn from(err: quick_xml::Error) -> Self {
+    GraphMlError::Xml(err)
+  }
+}
+
+impl From<quick_xml::events::attributes::AttrError> for GraphMlError {
+  fn from(err: quick_xml::events::attributes::AttrError) -> Self {
+    GraphMlError::Xml(err.into())
+  }
+}
+
+/// Writes the treeize as GraphML document.
+///
+/// Node titles are taken from `viewer`.
+/// `keys` declares user-defined node attributes and `attributes` returns
+/// `(name, value)` pairs for each node. Pairs with undeclared names are skipped.
+///
+/// # Errors
+///
+/// Returns [`GraphMlError::Io`] if writing fails.
+///
+/// # Examples
+///
+/// ```
+/// # use egui::Ui;
+/// # use egui_treeize::{InPin, InPinId, OutPin, OutPinId, Treeize};
+/// # use egui_treeize::graphml::{GraphMlKey, GraphMlType, export_graphml, import_graphml};
+/// # use egui_treeize::ui::{PinInfo, TreeizeViewer};
+/// struct Viewer;
+///
+/// impl TreeizeViewer<String> for Viewer {
+///   fn title(&mut self, node: &String) -> String {
+///     node.clone()
+///   }
+///
+///   fn has_input(&mut self, _: &String) -> bool {
+///     true
+///   }
+///
+///   fn has_output(&mut self, _: &String) -> bool {
+///     true
+///   }
+///
+///   fn show_input(&mut self, _: &InPin, _: &mut Ui, _: &mut Treeize<String>) -> PinInfo {
+///     PinInfo::default()
+///   }
+///
+///   fn show_output(&mut self, _: &OutPin, _: &mut Ui, _: &mut Treeize<String>) -> PinInfo {
+///     PinInfo::default()
+///   }
+/// }
+///
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(10.0, -2.5), "Root".to_owned());
+/// let child = treeize.insert_node_collapsed(egui::pos2(40.0, 80.0), "Child & co".to_owned());
+/// treeize.connect(OutPinId { node: root, output: 1 }, InPinId { node: child, input: 2 });
+///
+/// let keys = [GraphMlKey::new("size", GraphMlType::Int)];
+/// let mut doc = Vec::new();
+/// let size = |_, title: &String| vec![("size".to_owned(), title.len().to_string())];
+/// export_graphml(&treeize, &mut Viewer, &keys, size, &mut doc)?;
+///
+/// let imported = import_graphml(doc.as_slice(), |node| node.clone())?;
+/// let nodes = imported.nodes().collect::<Vec<_>>();
+/// assert_eq!(nodes[0].pos, egui::pos2(10.0, -2.5));
+/// assert_eq!(nodes[1].title.as_deref(), Some("Child & co"));
+/// assert!(nodes[0].open && !nodes[1].open);
+/// assert_eq!(nodes[1].attributes["size"], "10");
+///
+/// let wires = imported.wires().collect::<Vec<_>>();
+/// assert_eq!(wires, [(OutPinId { node: root, output: 1 }, InPinId { node: child, input: 2 })]);
+/// # Ok::<(), egui_treeize::graphml::GraphMlError>(())
+/// ```
+pub fn export_graphml<T, V, W>(
+  treeize: &Treeize<T>,
+  viewer: &mut V,
+  keys: &[GraphMlKey],
+  mut attributes: impl FnMut(NodeId, &T) -> Vec<(String, String)>,
+  mut writer: W,
+) -> Result<(), GraphMlError>
+where
+  V: TreeizeViewer<T>,
+  W: Write,
+{
+  writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(
+    writer,
+    r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">"#
+  )?;
+
+  let builtin = [
+    (KEY_X, GraphMlType::Double),
+    (KEY_Y, GraphMlType::Double),
+    (KEY_TITLE, GraphMlType::String),
+    (KEY_OPEN, GraphMlType::Boolean),
+  ];
+
+  let mut key_ids = HashMap::new();
+  for (name, ty) in builtin.into_iter().chain(keys.iter().map(|key| (key.name.as_str(), key.ty))) {
+    if key_ids.contains_key(name) {
+      continue;
+    }
+    let id = format!("d{}", key_ids.len());
+    writeln!(
+      writer,
+      r#"  <key id="{id}" for="node" attr.name="{}" attr.type="{}"/>"#,
+      escape(name),
+      ty.as_str()
+    )?;
+    key_ids.insert(name, id);
+  }
+
+  writeln!(writer, r#"  <graph id="G" edgedefault="directed">"#)?;
+
+  // Indices of input and output pins of each node that have wires attached.
+  let mut pins: HashMap<NodeId, (Vec<usize>, Vec<usize>)> = HashMap::new();
+  for (out_pin, in_pin) in treeize.sorted_wires() {
+    pins.entry(in_pin.node).or_default().0.push(in_pin.input);
+    pins.entry(out_pin.node).or_default().1.push(out_pin.output);
+  }
+
+  for (node, info) in treeize.nodes_ids_data() {
+    writeln!(writer, r#"    <node id="n{}">"#, node.0)?;
+
+    let (mut inputs, mut outputs) = pins.remove(&node).unwrap_or_default();
+    if viewer.has_input(&info.value) {
+      inputs.push(0);
+    }
+    if viewer.has_output(&info.value) {
+      outputs.push(0);
+    }
+    inputs.sort_unstable();
+    inputs.dedup();
+    outputs.sort_unstable();
+    outputs.dedup();
+
+    for input in inputs {
+      writeln!(writer, r#"      <port name="in{input}"/>"#)?;
+    }
+    for output in outputs {
+      writeln!(writer, r#"      <port name="out{output}"/>"#)?;
+    }
+
+    let title = viewer.title(&info.value);
+    let builtin_values = [
+      (KEY_X, info.pos.x.to_string()),
+      (KEY_Y, info.pos.y.to_string()),
+      (KEY_TITLE, title),
+      (KEY_OPEN, info.open.to_string()),
+    ];
+
+    for (name, value) in builtin_values {
+      writeln!(writer, r#"      <data key="{}">{}</data>"#, key_ids[name], escape(value))?;
+    }
+
+    for (name, value) in attributes(node, &info.value) {
+      if KEY_NAMES.contains(&name.as_str()) {
+        continue;
+      }
+      if let Some(id) = key_ids.get(name.as_str()) {
+        writeln!(writer, r#"      <data key="{id}">{}</data>"#, escape(value))?;
+      }
+    }
+
+    writeln!(writer, "    </node>")?;
+  }
+
+  for (idx, (out_pin, in_pin)) in treeize.sorted_wires().enumerate() {
+    writeln!(
+      writer,
+      r#"    <edge id="e{idx}" source="n{}" target="n{}" sourceport="out{}" targetport="in{}"/>"#,
+      out_pin.node.0, in_pin.node.0, out_pin.output, in_pin.input
+    )?;
+  }
+
+  writeln!(writer, "  </graph>")?;
+  writeln!(writer, "</graphml>")?;
+  writer.flush()?;
+
+  Ok(())
+}
+
+const KEY_NAMES: [&str; 4] = [KEY_X, KEY_Y, KEY_TITLE, KEY_OPEN];
+
+/// Edge read from GraphML document, before node ids are resolved.
+struct GraphMlEdge {
+  source: String,
+  target: String,
+  output: usize,
+  input: usize,
+}
+
+/// Declared attribute key.
+struct KeyDecl {
+  name: String,
+  default: Option<String>,
+  /// Whether the key applies to nodes, according to its `for` attribute.
+  for_node: bool,
+}
+
+/// Element in the document that is currently open.
+enum Scope {
+  Key(String),
+  KeyDefault(String),
+  Node(GraphMlNode),
+  Data {
+    key: String,
+    node: GraphMlNode,
+  },
+  /// yEd `<y:NodeLabel>` nested in node data.
+  Label,
+  Other,
+}
+
+/// Reads GraphML document into a new [`Treeize`].
+///
+/// `node` callback maps each [`GraphMlNode`] with its attributes to the node value.
+/// Only the first graph in the document is read, nested graphs and hyperedges are ignored.
+///
+/// Edge ports named `outN` and `inN` are mapped to pin index `N`.
+/// Edges without ports connect pins with index `0`.
+///
+/// # Errors
+///
+/// Returns [`GraphMlError::Xml`] if the document is malformed
+/// and [`GraphMlError::Invalid`] if edges reference unknown nodes.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::graphml::import_graphml;
+/// let doc = r#"
+/// <graphml>
+///   <key id="d0" for="node" attr.name="x" attr.type="double"/>
+///   <key id="d1" for="node" attr.name="title" attr.type="string"/>
+///   <graph edgedefault="directed">
+///     <node id="a"><data key="d0">10</data><data key="d1">Root</data></node>
+///     <node id="b"><data key="d1">Child</data></node>
+///     <edge source="a" target="b"/>
+///   </graph>
+/// </graphml>"#;
+///
+/// let treeize = import_graphml(doc.as_bytes(), |node| node.title.clone().unwrap_or_default())?;
+/// assert_eq!(treeize.nodes().count(), 2);
+/// assert_eq!(treeize.wires().count(), 1);
+///
+/// // Defaults of keys declared for edges are not node attributes.
+/// let doc = r#"
+/// <graphml>
+///   <key id="w" for="edge" attr.name="weight" attr.type="double"><default>1.0</default></key>
+///   <key id="c" attr.name="color" attr.type="string"><default>red</default></key>
+///   <graph edgedefault="directed"><node id="a"/></graph>
+/// </graphml>"#;
+///
+/// let treeize = import_graphml(doc.as_bytes(), |node| node.attributes.clone())?;
+/// let attributes = treeize.nodes().next().unwrap();
+/// assert!(!attributes.contains_key("weight"));
+/// assert_eq!(attributes["color"], "red");
+/// # Ok::<(), egui_treeize::graphml::GraphMlError>(())
+/// ```
+#[allow(clippy::too_many_lines)]
+pub fn import_graphml<T, R>(
+  reader: R,
+  mut node: impl FnMut(&GraphMlNode) -> T,
+) -> Result<Treeize<T>, GraphMlError>
+where
+  R: BufRead,
+{
+  let mut reader = Reader::from_reader(reader);
+  reader.config_mut().trim_text(true);
+
+  let mut keys: HashMap<String, KeyDecl> = HashMap::new();
+  let mut nodes: Vec<GraphMlNode> = Vec::new();
+  let mut edges: Vec<GraphMlEdge> = Vec::new();
+
+  let mut stack: Vec<Scope> = Vec::new();
+  let mut graph_depth = None;
+  let mut graph_done = false;
+
+  let mut buf = Vec::new();
+  loop {
+    let event = reader.read_event_into(&mut buf)?;
+    match event {
+      Event::Start(ref e) | Event::Empty(ref e) => {
+        let empty = matches!(event, Event::Empty(_));
+        let in_graph = graph_depth.is_some_and(|depth| stack.len() == depth + 1);
+
+        let scope = match e.local_name().as_ref() {
+          b"key" if graph_depth.is_none() => {
+            let id = attr(e, b"id")?.unwrap_or_default();
+            let name = attr(e, b"attr.name")?.unwrap_or_else(|| id.clone());
+            let for_node =
+              attr(e, b"for")?.is_none_or(|domain| domain == "node" || domain == "all");
+            keys.insert(id.clone(), KeyDecl { name, default: None, for_node });
+            Scope::Key(id)
+          }
+          b"default" => match stack.last() {
+            Some(Scope::Key(id)) => Scope::KeyDefault(id.clone()),
+            _ => Scope::Other,
+          },
+          b"graph" if graph_depth.is_none() && !graph_done => {
+            graph_depth = Some(stack.len());
+            Scope::Other
+          }
+          b"node" if in_graph => {
+            let id =
+              attr(e, b"id")?.ok_or_else(|| GraphMlError::Invalid("node without id".to_owned()))?;
+            let mut graph_node = GraphMlNode { id, ..GraphMlNode::default() };
+            for decl in keys.values().filter(|decl| decl.for_node) {
+              if let Some(default) = &decl.default {
+                apply_attribute(&mut graph_node, &decl.name, default);
+              }
+            }
+            Scope::Node(graph_node)
+          }
+          b"edge" if in_graph => {
+            let source = attr(e, b"source")?
+              .ok_or_else(|| GraphMlError::Invalid("edge without source".to_owned()))?;
+            let target = attr(e, b"target")?
+              .ok_or_else(|| GraphMlError::Invalid("edge without target".to_owned()))?;
+            let output = port_index(attr(e, b"sourceport")?.as_deref(), "out");
+            let input = port_index(attr(e, b"targetport")?.as_deref(), "in");
+            edges.push(GraphMlEdge { source, target, output, input });
+            Scope::Other
+          }
+          // yEd stores geometry and label in its own elements nested in node data.
+          b"Geometry" => {
+            let x = attr(e, b"x")?.and_then(|x| x.parse().ok());
+            let y = attr(e, b"y")?.and_then(|y| y.parse().ok());
+            if let Some(graph_node) = data_node(&mut stack) {
+              graph_node.pos.x = x.unwrap_or(graph_node.pos.x);
+              graph_node.pos.y = y.unwrap_or(graph_node.pos.y);
+            }
+            Scope::Other
+          }
+          b"NodeLabel" => Scope::Label,
+          b"data" => match stack.pop() {
+            Some(Scope::Node(graph_node)) => {
+              let key = attr(e, b"key")?.unwrap_or_default();
+              Scope::Data { key, node: graph_node }
+            }
+            Some(scope) => {
+              stack.push(scope);
+              Scope::Other
+            }
+            None => Scope::Other,
+          },
+          _ => Scope::Other,
+        };
+
+        if empty {
+          close_scope(scope, &mut stack, &mut nodes);
+          if graph_depth == Some(stack.len()) {
+            graph_depth = None;
+            graph_done = true;
+          }
+        } else {
+          stack.push(scope);
+        }
+      }
+      Event::Text(e) => {
+        let text = e.unescape()?;
+        match stack.last_mut() {
+          Some(Scope::Data { key, node }) => {
+            if let Some(decl) = keys.get(key.as_str()) {
+              apply_attribute(node, &decl.name, &text);
+            }
+          }
+          Some(Scope::KeyDefault(id)) => {
+            if let Some(decl) = keys.get_mut(id.as_str()) {
+              decl.default = Some(text.into_owned());
+            }
+          }
+          Some(Scope::Label) => {
+            if let Some(graph_node) = data_node(&mut stack)
+              && graph_node.title.is_none()
+            {
+              graph_node.title = Some(text.into_owned());
+            }
+          }
+          _ => {}
+        }
+      }
+      Event::End(_) => {
+        if let Some(scope) = stack.pop() {
+          close_scope(scope, &mut stack, &mut nodes);
+        }
+        if graph_depth == Some(stack.len()) {
+          graph_depth = None;
+          graph_done = true;
+        }
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+    buf.clear();
+  }
+
+  let mut treeize = Treeize::new();
+  let mut ids = HashMap::new();
+
+  for graph_node in &nodes {
+    let value = node(graph_node);
+    let id = if graph_node.open {
+      treeize.insert_node(graph_node.pos, value)
+    } else {
+      treeize.insert_node_collapsed(graph_node.pos, value)
+    };
+    ids.insert(graph_node.id.as_str(), id);
+  }
+
+  for edge in &edges {
+    let resolve = |id: &str| {
+      ids
+        .get(id)
+        .copied()
+        .ok_or_else(|| GraphMlError::Invalid(format!("edge references unknown node `{id}`")))
+    };
+
+    let from = OutPinId { node: resolve(&edge.source)?, output: edge.output };
+    let to = InPinId { node: resolve(&edge.target)?, input: edge.input };
+    treeize.connect(from, to);
+  }
+
+  Ok(treeize)
+}
+
+fn attr(e: &BytesStart, name: &[u8]) -> Result<Option<String>, GraphMlError> {
+  for attribute in e.attributes() {
+    let attribute = attribute?;
+    if attribute.key.as_ref() == name {
+      return Ok(Some(attribute.unescape_value()?.into_owned()));
+    }
+  }
+  Ok(None)
+}
+
+fn port_index(port: Option<&str>, prefix: &str) -> usize {
+  port.and_then(|port| port.strip_prefix(prefix)).and_then(|idx| idx.parse().ok()).unwrap_or(0)
+}
+
+fn apply_attribute(node: &mut GraphMlNode, name: &str, value: &str) {
+  match name {
+    KEY_X => node.pos.x = value.trim().parse().unwrap_or(node.pos.x),
+    KEY_Y => node.pos.y = value.trim().parse().unwrap_or(node.pos.y),
+    KEY_TITLE => node.title = Some(value.to_owned()),
+    KEY_OPEN => node.open = value.trim() != "false",
+    _ => {}
+  }
+  node.attributes.insert(name.to_owned(), value.to_owned());
+}
+
+/// Returns node which data element is currently open.
+fn data_node(stack: &mut [Scope]) -> Option<&mut GraphMlNode> {
+  stack.iter_mut().rev().find_map(|scope| match scope {
+    Scope::Data { node, .. } => Some(node),
+    _ => None,
+  })
+}
+
+fn close_scope(scope: Scope, stack: &mut Vec<Scope>, nodes: &mut Vec<GraphMlNode>) {
+  match scope {
+    Scope::Node(graph_node) => nodes.push(graph_node),
+    Scope::Data { node, .. } => stack.push(Scope::Node(node)),
+    Scope::Key(_) | Scope::KeyDefault(_) | Scope::Label | Scope::Other => {}
+  }
+}
+
+impl Default for GraphMlNode {
+  fn default() -> Self {
+    GraphMlNode {
+      id: String::new(),
+      pos: pos2(0.0, 0.0),
+      title: None,
+      open: true,
+      attributes: HashMap::new(),
+    }
+  }
+}
@@ -1,32 +1,433 @@
 //! Layout algorithms for tree-like graphs.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use egui::{Context, Id, Pos2, Vec2, pos2};
+use egui::{Context, Id, Pos2, Rect, Vec2, pos2, vec2};
+
+mod constraint;
+mod force;
+mod graph;
+mod layered;
+mod packing;
+mod radial;
+mod result;
+mod subtree;
+mod wrap;
+
+pub use self::{
+  force::{ForceConfig, ForceSimulation},
+  graph::{LayoutGraph, TreeLayout},
+  result::LayoutResult,
+};
 
-use crate::ui::TreeizeViewer;
 use crate::ui::state::NodeState;
-use crate::{NodeId, Treeize};
+use crate::ui::{NodeLayoutKind, TreeizeViewer};
+use crate::{InPinId, NodeId, OutPinId, Treeize};
+
+/// Direction in which the tree grows from roots to leaves.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, LayoutOrientation, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+///
+/// let config = LayoutConfig { orientation: LayoutOrientation::LeftToRight, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// assert!(positions[&child].x > positions[&root].x);
+/// assert_eq!(positions[&child].y, positions[&root].y);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum LayoutOrientation {
+  /// Roots at the top, leaves below them.
+  #[default]
+  TopToBottom,
+
+  /// Roots at the bottom, leaves above them.
+  BottomToTop,
+
+  /// Roots on the left, leaves to the right of them.
+  LeftToRight,
+
+  /// Roots on the right, leaves to the left of them.
+  RightToLeft,
+}
+
+impl LayoutOrientation {
+  /// Returns true if levels of the tree are placed along the x axis.
+  #[must_use]
+  pub const fn is_horizontal(self) -> bool {
+    matches!(self, LayoutOrientation::LeftToRight | LayoutOrientation::RightToLeft)
+  }
+
+  /// Returns [`NodeLayoutKind`] with pins placed to match this orientation.
+  #[must_use]
+  pub const fn node_layout_kind(self) -> NodeLayoutKind {
+    match self {
+      LayoutOrientation::TopToBottom => NodeLayoutKind::Compact,
+      LayoutOrientation::BottomToTop => NodeLayoutKind::BottomToTop,
+      LayoutOrientation::LeftToRight => NodeLayoutKind::LeftToRight,
+      LayoutOrientation::RightToLeft => NodeLayoutKind::RightToLeft,
+    }
+  }
+
+  /// Maps top-left corner of a node from the top-to-bottom layout into this orientation.
+  ///
+  /// `pos` and `size` are given with x along levels and y across them.
+  fn orient(self, start_pos: Pos2, pos: Pos2, size: Vec2) -> Pos2 {
+    let across = pos.x - start_pos.x;
+    let along = pos.y - start_pos.y;
+
+    match self {
+      LayoutOrientation::TopToBottom => pos,
+      LayoutOrientation::BottomToTop => pos2(pos.x, start_pos.y - along - size.y),
+      LayoutOrientation::LeftToRight => pos2(start_pos.x + along, start_pos.y + across),
+      LayoutOrientation::RightToLeft => pos2(start_pos.x - along - size.y, start_pos.y + across),
+    }
+  }
+}
+
+/// Algorithm used to arrange nodes.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutAlgorithm, LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// for _ in 0..4 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+///
+/// let config = LayoutConfig { algorithm: LayoutAlgorithm::Radial, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// // Children surround the root.
+/// assert!(positions.values().any(|pos| pos.x < positions[&root].x));
+/// assert!(positions.values().any(|pos| pos.y > positions[&root].y));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum LayoutAlgorithm {
+  /// Tidy tree with each parent centered above its children.
+  /// A node with several parents is placed under the first one.
+  #[default]
+  Tidy,
+
+  /// Layered (Sugiyama) layout for directed acyclic graphs.
+  /// Nodes with several parents are placed below all of them
+  /// and crossings of wires are minimized.
+  Layered,
+
+  /// Radial layout with roots in the centers and each depth on a ring.
+  /// Suits wide and shallow trees. Orientation is ignored.
+  Radial,
+
+  /// Force-directed layout for free-form exploration of loosely hierarchical graphs,
+  /// configured by [`LayoutConfig::force`].
+  /// Starts from current positions and takes time quadratic in the number of nodes
+  /// for each iteration. The widget runs it over several frames with [`ForceSimulation`].
+  Force,
+}
+
+/// Where a parent is placed relative to its children in [`LayoutAlgorithm::Tidy`].
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, ParentAlignment, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// for _ in 0..3 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+///
+/// let config = LayoutConfig { parent_alignment: ParentAlignment::Start, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// // The root is aligned with the leftmost child.
+/// let leftmost = positions.values().map(|pos| pos.x).fold(f32::INFINITY, f32::min);
+/// assert_eq!(positions[&root].x, leftmost);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum ParentAlignment {
+  /// Centered over the middle between its first and last children.
+  #[default]
+  Center,
+
+  /// Centered over its first child.
+  FirstChild,
+
+  /// Centered over its last child.
+  LastChild,
+
+  /// Aligned with the starting edge of its first child,
+  /// the left edge in top-to-bottom trees.
+  Start,
+}
+
+/// How levels of a tree are placed along the direction of [`LayoutAlgorithm::Tidy`] layout.
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, LevelAlignment, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let tall = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let short = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let (a, b) = (treeize.insert_node(egui::pos2(0.0, 0.0), ()), treeize.insert_node(egui::pos2(0.0, 0.0), ()));
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: tall, input: 0 });
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: short, input: 0 });
+/// treeize.connect(OutPinId { node: tall, output: 0 }, InPinId { node: a, input: 0 });
+/// treeize.connect(OutPinId { node: short, output: 0 }, InPinId { node: b, input: 0 });
+///
+/// let mut sizes = HashMap::new();
+/// for node in [root, short, a, b] {
+///   sizes.insert(node, egui::vec2(100.0, 50.0));
+/// }
+/// sizes.insert(tall, egui::vec2(100.0, 300.0));
+///
+/// let config = LayoutConfig { level_alignment: LevelAlignment::Aligned, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, Some(&sizes)).positions;
+/// assert_eq!(positions[&a].y, positions[&b].y);
+///
+/// let positions =
+///   layout_tree(&treeize, LayoutConfig::default(), |_| true, |_| true, Some(&sizes)).positions;
+/// assert!(positions[&a].y > positions[&b].y);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum LevelAlignment {
+  /// Children are placed right after their parent,
+  /// so nodes of the same depth may be at different levels.
+  #[default]
+  Compact,
+
+  /// All nodes of the same depth start at the same level,
+  /// which fits the largest node of the previous depth.
+  Aligned,
+}
+
+/// How trees of different roots are placed relative to each other.
+///
+/// Trees connected by wires, e.g. through shared children, are packed together.
+/// Packed trees start at [`LayoutConfig::start_pos`] and extend right and down
+/// whatever the orientation, separated by the sum of [`LayoutConfig::horizontal_spacing`]
+/// and [`LayoutConfig::subtree_spacing`].
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::Treeize;
+/// # use egui_treeize::layout::{ForestPacking, LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// for _ in 0..16 {
+///   treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// }
+///
+/// let config = LayoutConfig {
+///   forest_packing: ForestPacking::Wrap,
+///   packing_aspect_ratio: 1.0,
+///   ..Default::default()
+/// };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// // Single nodes of the same size are wrapped into a square of 4 rows.
+/// let mut rows = positions.values().map(|pos| pos.y as i32).collect::<Vec<_>>();
+/// rows.sort();
+/// rows.dedup();
+/// assert_eq!(rows.len(), 4);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum ForestPacking {
+  /// Trees are placed in one row, as arranged by the [`LayoutAlgorithm`].
+  #[default]
+  Row,
+
+  /// Trees are placed in rows in their order,
+  /// wrapped for all rows to fit [`LayoutConfig::packing_aspect_ratio`].
+  Wrap,
+
+  /// Trees are sorted from the tallest and packed into shelves,
+  /// each into the first shelf with room for it.
+  /// Shelves are as wide as rows of [`ForestPacking::Wrap`].
+  Shelf,
+}
+
+/// How children of a parent with many of them are arranged by [`LayoutAlgorithm::Tidy`].
+///
+/// Wrapping applies to parents with more than [`LayoutConfig::max_siblings`] children.
+/// Wrapped children are split into rows, each row into halves on both sides of a gap
+/// below the middle of the parent, where wires can pass down to lower rows.
+/// Subtrees of wrapped children are arranged on their own and kept intact,
+/// and [`LevelAlignment::Aligned`] does not apply to them.
+///
+/// Wires drawn with [`WireStyle::Routed`](crate::ui::WireStyle::Routed) and paths
+/// in [`LayoutResult::wires`] go down the gap and turn right above the row of the child.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, SiblingWrapping, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// for _ in 0..30 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+///
+/// let config =
+///   LayoutConfig { sibling_wrapping: SiblingWrapping::Rows, max_siblings: 10, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// // Children are wrapped into 3 rows of 10.
+/// let mut rows = positions.values().map(|pos| pos.y as i32).collect::<Vec<_>>();
+/// rows.sort();
+/// rows.dedup();
+/// assert_eq!(rows.len(), 1 + 3);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum SiblingWrapping {
+  /// All children are placed in one row.
+  #[default]
+  Off,
+
+  /// Children are wrapped into rows of at most [`LayoutConfig::max_siblings`].
+  Rows,
+
+  /// Children are stacked in a column, one on each side of the gap in every row,
+  /// like assistants in an org chart.
+  /// Only parents whose children are all leaves are wrapped.
+  LeafColumn,
+}
 
 /// Configuration for tree layout algorithm.
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct LayoutConfig {
-  /// Horizontal spacing between nodes at the same level.
+  /// Spacing between nodes at the same level.
+  /// Horizontal for vertical orientations and vertical for horizontal ones.
   pub horizontal_spacing: f32,
 
-  /// Vertical spacing between levels.
+  /// Additional spacing between neighboring nodes that are not siblings,
+  /// and between trees of different roots.
+  /// Only used by [`LayoutAlgorithm::Tidy`].
+  pub subtree_spacing: f32,
+
+  /// Spacing between levels.
+  /// Vertical for vertical orientations and horizontal for horizontal ones.
   pub vertical_spacing: f32,
 
   /// Starting position for the layout.
+  /// Roots are placed next to it in the direction of [`LayoutConfig::orientation`].
   pub start_pos: Pos2,
+
+  /// Direction in which the tree grows.
+  pub orientation: LayoutOrientation,
+
+  /// Algorithm used to arrange nodes.
+  pub algorithm: LayoutAlgorithm,
+
+  /// Where parents are placed relative to their children.
+  /// Only used by [`LayoutAlgorithm::Tidy`].
+  pub parent_alignment: ParentAlignment,
+
+  /// How levels of trees are placed.
+  /// Only used by [`LayoutAlgorithm::Tidy`].
+  pub level_alignment: LevelAlignment,
+
+  /// How trees of different roots are placed relative to each other.
+  pub forest_packing: ForestPacking,
+
+  /// Width to height ratio that [`ForestPacking`] and the singleton grid aim for.
+  pub packing_aspect_ratio: f32,
+
+  /// Gather nodes without wires into one grid,
+  /// packed as a single tree by [`LayoutConfig::forest_packing`].
+  pub singleton_grid: bool,
+
+  /// Leave nodes hidden in subtrees of collapsed nodes out of the layout.
+  /// See [`Treeize::folded_nodes`].
+  ///
+  /// Hidden nodes are moved along with the collapsed node that hides them,
+  /// so expanding it shows the subtree in the same place relative to it.
+  pub fold_collapsed: bool,
+
+  /// Forces of [`LayoutAlgorithm::Force`].
+  pub force: ForceConfig,
+
+  /// How children of parents with many of them are arranged.
+  /// Only used by [`LayoutAlgorithm::Tidy`].
+  pub sibling_wrapping: SiblingWrapping,
+
+  /// Number of children a parent may have before [`LayoutConfig::sibling_wrapping`] wraps them,
+  /// and the most children in a row of wrapped ones.
+  pub max_siblings: usize,
 }
 
 impl Default for LayoutConfig {
   fn default() -> Self {
-    LayoutConfig { horizontal_spacing: 200.0, vertical_spacing: 150.0, start_pos: Pos2::ZERO }
+    LayoutConfig {
+      horizontal_spacing: 200.0,
+      subtree_spacing: 0.0,
+      vertical_spacing: 150.0,
+      start_pos: Pos2::ZERO,
+      orientation: LayoutOrientation::TopToBottom,
+      algorithm: LayoutAlgorithm::Tidy,
+      parent_alignment: ParentAlignment::Center,
+      level_alignment: LevelAlignment::Compact,
+      forest_packing: ForestPacking::Row,
+      packing_aspect_ratio: 16.0 / 9.0,
+      singleton_grid: false,
+      fold_collapsed: false,
+      force: ForceConfig::default(),
+      sibling_wrapping: SiblingWrapping::Off,
+      max_siblings: 16,
+    }
   }
 }
 
+/// Constraint on the position of a single node, see [`layout_tree_constrained`].
+///
+/// A constrained node carries its subtree with it,
+/// and descendants may have constraints of their own.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayoutConstraint {
+  /// Node is placed with its top-left corner at this position.
+  Pinned(Pos2),
+
+  /// Node is placed with its left edge at this x coordinate.
+  /// The other coordinate comes from the layout.
+  FixedX(f32),
+
+  /// Node keeps its current offset from its parent in the layout.
+  /// Constrains nothing for roots.
+  KeepParentOffset,
+}
+
 /// Node dimension provider for the layout algorithm.
 /// This trait allows the layout algorithm to query node sizes and children.
 trait NodeDimensionProvider {
@@ -35,217 +436,546 @@ trait NodeDimensionProvider {
 
   /// Returns the list of child node IDs for a given node.
   fn get_children(&self, node_id: NodeId) -> Vec<NodeId>;
+
+  /// Returns weight of the wire from `parent` to `child`, see [`LayoutGraph::weight`].
+  fn get_weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    let _ = (parent, child);
+    1.0
+  }
 }
 
-/// Adapter that implements `NodeDimensionProvider` for `Treeize`.
-struct TreeizeAdapter<'a, T> {
-  node_sizes: Option<&'a HashMap<NodeId, Vec2>>,
-  children_map: HashMap<NodeId, Vec<NodeId>>,
-  config: LayoutConfig,
-  _phantom: std::marker::PhantomData<T>,
+/// Spanning forest of the graph found by depth-first search.
+///
+/// Roots are nodes without parents, followed by nodes reachable only through cycles.
+/// Edges that close cycles are collected as back edges and left out of the forest,
+/// so the layout always terminates. A node with several parents is attached to the first
+/// parent that reaches it.
+struct SpanningForest<'a, P> {
+  provider: &'a P,
+  roots: Vec<NodeId>,
+  children: HashMap<NodeId, Vec<NodeId>>,
+  parents: HashMap<NodeId, NodeId>,
+  back_edges: HashSet<(NodeId, NodeId)>,
 }
 
-impl<'a, T> TreeizeAdapter<'a, T> {
-  fn new(
-    treeize: &Treeize<T>,
-    node_sizes: Option<&'a HashMap<NodeId, Vec2>>,
-    has_output: &mut impl FnMut(NodeId) -> bool,
-    has_input: &mut impl FnMut(NodeId) -> bool,
-    config: LayoutConfig,
-  ) -> Self {
-    // Build children map from wires
-    let mut children_map: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+impl<'a, P: NodeDimensionProvider> SpanningForest<'a, P> {
+  fn new(nodes: &[NodeId], provider: &'a P) -> Self {
+    let mut has_parent = HashSet::new();
+    for &node in nodes {
+      has_parent.extend(provider.get_children(node).into_iter().filter(|&child| child != node));
+    }
 
-    for (out_pin, in_pin) in treeize.wires() {
-      let from_node = out_pin.node;
-      let to_node = in_pin.node;
+    let mut forest = SpanningForest {
+      provider,
+      roots: Vec::new(),
+      children: HashMap::new(),
+      parents: HashMap::new(),
+      back_edges: HashSet::new(),
+    };
 
-      if has_output(from_node) && has_input(to_node) {
-        children_map.entry(from_node).or_default().push(to_node);
+    let mut visited = HashSet::new();
+    let mut on_stack = HashSet::new();
+
+    let starts = nodes.iter().filter(|node| !has_parent.contains(*node)).chain(nodes);
+    for &start in starts {
+      if !visited.insert(start) {
+        continue;
+      }
+
+      forest.roots.push(start);
+      on_stack.insert(start);
+
+      let mut stack = vec![(start, provider.get_children(start), 0)];
+      while let Some((node, children, next)) = stack.last_mut() {
+        let node = *node;
+        let Some(&child) = children.get(*next) else {
+          on_stack.remove(&node);
+          stack.pop();
+          continue;
+        };
+        *next += 1;
+
+        if visited.insert(child) {
+          forest.children.entry(node).or_default().push(child);
+          forest.parents.insert(child, node);
+          on_stack.insert(child);
+          stack.push((child, provider.get_children(child), 0));
+        } else if on_stack.contains(&child) {
+          forest.back_edges.insert((node, child));
+        }
       }
     }
 
-    TreeizeAdapter { node_sizes, children_map, config, _phantom: std::marker::PhantomData }
+    forest
   }
 }
 
-impl<T> NodeDimensionProvider for TreeizeAdapter<'_, T> {
+impl<P: NodeDimensionProvider> NodeDimensionProvider for SpanningForest<'_, P> {
   fn get_size(&self, node_id: NodeId) -> (f32, f32) {
-    if let Some(sizes) = self.node_sizes
-      && let Some(size) = sizes.get(&node_id)
-    {
-      return (size.x, size.y);
-    }
-    // Default size if not provided
-    (self.config.horizontal_spacing, self.config.vertical_spacing)
+    self.provider.get_size(node_id)
   }
 
   fn get_children(&self, node_id: NodeId) -> Vec<NodeId> {
-    self.children_map.get(&node_id).cloned().unwrap_or_default()
+    self.children.get(&node_id).cloned().unwrap_or_default()
+  }
+
+  fn get_weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    self.provider.get_weight(parent, child)
   }
 }
 
-/// Internal layout node structure.
-struct LayoutNode {
-  /// X offset relative to parent node's center.
-  offset_x: f32,
-  /// Contour: list of `(left_edge, right_edge)` for each depth level.
-  /// Depth 0 is the node itself, depth 1 is its children, etc.
-  contour: Vec<(f32, f32)>,
+/// Maps coordinate to an integer with the same order, to index rects by their sides.
+fn sort_key(x: f32) -> u32 {
+  let bits = x.to_bits();
+  if bits >> 31 == 1 { !bits } else { bits | 1 << 31 }
+}
+
+/// Node of the tree being arranged by [`TidyTree`].
+///
+/// Sizes are padded by spacing, so padded nodes may touch but never overlap.
+struct TidyNode {
+  /// Node of the graph, `None` for a block of wrapped children.
+  id: Option<NodeId>,
+  children: Vec<usize>,
+  depth: usize,
   width: f32,
   height: f32,
+  x: f32,
+  y: f32,
+  prelim: f32,
+  modifier: f32,
+  shift: f32,
+  change: f32,
+  /// Next node of the left contour if this node is a leaf.
+  thread_left: Option<usize>,
+  /// Next node of the right contour if this node is a leaf.
+  thread_right: Option<usize>,
+  /// Lowest node of the left contour of the subtree.
+  extreme_left: usize,
+  /// Lowest node of the right contour of the subtree.
+  extreme_right: usize,
+  /// Sum of modifiers down to `extreme_left`.
+  modsum_left: f32,
+  /// Sum of modifiers down to `extreme_right`.
+  modsum_right: f32,
 }
 
-/// Layout state that stores intermediate calculations.
-struct LayoutState {
-  nodes: HashMap<NodeId, LayoutNode>,
+impl TidyNode {
+  fn bottom(&self) -> f32 {
+    self.y + self.height
+  }
 }
 
-impl LayoutState {
-  fn new() -> Self {
-    Self { nodes: HashMap::new() }
-  }
+/// Entry of the list of left siblings that are still visible on the right contour,
+/// with the lowest y coordinate of their subtrees.
+struct LowestY {
+  low_y: f32,
+  index: usize,
+  next: Option<usize>,
 }
 
-/// First walk: bottom-up traversal to calculate contours and offsets.
-fn first_walk<P: NodeDimensionProvider>(
-  node_id: NodeId,
-  provider: &P,
-  config: &LayoutConfig,
-  state: &mut LayoutState,
-) {
-  let (w, h) = provider.get_size(node_id);
-  let children = provider.get_children(node_id);
-
-  if children.is_empty() {
-    // Leaf node: contour is just the node itself
-    state.nodes.insert(
-      node_id,
-      LayoutNode { offset_x: 0.0, contour: vec![(-w / 2.0, w / 2.0)], width: w, height: h },
-    );
-    return;
+/// Non-layered tidy tree layout.
+///
+/// Implements the Buchheim-Walker algorithm with threads and apportioning,
+/// extended by van der Ploeg to nodes of variable sizes.
+/// Runs in linear time and does not recurse, so deep trees can't overflow the stack.
+struct TidyTree {
+  nodes: Vec<TidyNode>,
+  /// Nodes of blocks of wrapped children, by index of the block.
+  blocks: HashMap<usize, Vec<(NodeId, Rect)>>,
+  lowest: Vec<LowestY>,
+  subtree_spacing: f32,
+  parent_alignment: ParentAlignment,
+  /// Number of steps taken along contours while separating subtrees.
+  contour_steps: usize,
+}
+
+impl TidyTree {
+  /// Builds spanning tree of nodes reachable from `root` and not yet in `visited`.
+  ///
+  /// `provider` is a [`SpanningForest`], so nodes with several parents are already
+  /// attached to the first parent found depth-first.
+  /// Children are wrapped as set by [`LayoutConfig::sibling_wrapping`].
+  fn new<P: NodeDimensionProvider>(
+    root: NodeId,
+    provider: &P,
+    config: &LayoutConfig,
+    visited: &mut HashSet<NodeId>,
+  ) -> Self {
+    let mut tree = TidyTree {
+      nodes: Vec::new(),
+      blocks: HashMap::new(),
+      lowest: Vec::new(),
+      subtree_spacing: config.subtree_spacing,
+      parent_alignment: config.parent_alignment,
+      contour_steps: 0,
+    };
+    visited.insert(root);
+    tree.push(root, 0, 0.0, provider, config);
+
+    let mut next = 0;
+    while next < tree.nodes.len() {
+      let parent = next;
+      next += 1;
+
+      let Some(parent_id) = tree.nodes[parent].id else {
+        continue;
+      };
+      let child_y = tree.nodes[parent].bottom();
+      let child_depth = tree.nodes[parent].depth + 1;
+      let children = provider
+        .get_children(parent_id)
+        .into_iter()
+        .filter(|&child_id| visited.insert(child_id))
+        .collect::<Vec<_>>();
+
+      if let Some(row_length) = wrap::row_length(&children, provider, config) {
+        let block = wrap::wrap(children, row_length, provider, config, visited);
+        let child = tree.push_block(block, child_depth, child_y);
+        tree.nodes[parent].children.push(child);
+        continue;
+      }
+
+      for child_id in children {
+        // Weighted wires stretch or shrink the spacing to the child.
+        let weight = provider.get_weight(parent_id, child_id).max(0.0);
+        let child_y = child_y + (weight - 1.0) * config.vertical_spacing;
+        let child = tree.push(child_id, child_depth, child_y, provider, config);
+        tree.nodes[parent].children.push(child);
+      }
+    }
+
+    tree
   }
 
-  // 1. Recursively process all children
-  for child_id in &children {
-    first_walk(*child_id, provider, config, state);
+  fn push<P: NodeDimensionProvider>(
+    &mut self,
+    id: NodeId,
+    depth: usize,
+    y: f32,
+    provider: &P,
+    config: &LayoutConfig,
+  ) -> usize {
+    let (w, h) = provider.get_size(id);
+    let size = vec2(w + config.horizontal_spacing, h + config.vertical_spacing);
+    self.push_node(Some(id), depth, y, size)
   }
 
-  // 2. Merge children contours (Contour Merge - the core of Mr.Tree algorithm)
-  let mut merged_contour: Vec<(f32, f32)> = Vec::new();
-  let mut child_offsets: Vec<f32> = Vec::new();
-  let mut current_offset = 0.0;
+  fn push_block(&mut self, block: wrap::Block, depth: usize, y: f32) -> usize {
+    let idx = self.push_node(None, depth, y, block.size);
+    self.blocks.insert(idx, block.nodes);
+    idx
+  }
 
-  for (i, child_id) in children.iter().enumerate() {
-    let child_node = state.nodes.get(child_id).unwrap();
+  fn push_node(&mut self, id: Option<NodeId>, depth: usize, y: f32, size: Vec2) -> usize {
+    let idx = self.nodes.len();
+    self.nodes.push(TidyNode {
+      id,
+      children: Vec::new(),
+      depth,
+      width: size.x,
+      height: size.y,
+      x: 0.0,
+      y,
+      prelim: 0.0,
+      modifier: 0.0,
+      shift: 0.0,
+      change: 0.0,
+      thread_left: None,
+      thread_right: None,
+      extreme_left: idx,
+      extreme_right: idx,
+      modsum_left: 0.0,
+      modsum_right: 0.0,
+    });
+    idx
+  }
 
-    if i == 0 {
-      // First subtree: use as-is
-      merged_contour.clone_from(&child_node.contour);
-      child_offsets.push(0.0);
-    } else {
-      // Subsequent subtrees: calculate minimum shift to avoid overlap
-      let mut shift = 0.0f32;
+  /// Arranges the tree with the root's subtree starting at x = 0.
+  fn layout(&mut self) {
+    self.first_walk();
+    self.second_walk();
+    debug_assert!(
+      self.contour_steps <= 4 * self.nodes.len(),
+      "separating subtrees took {} contour steps for {} nodes",
+      self.contour_steps,
+      self.nodes.len()
+    );
+  }
 
-      // Check all overlapping depth levels
-      for ((_l1, r1), (l2, _r2)) in merged_contour.iter().zip(child_node.contour.iter()) {
-        // Calculate required distance: r1 + gap - l2
-        let dist = (r1 + config.horizontal_spacing) - l2;
-        if dist > shift {
-          shift = dist;
-        }
+  /// Returns rects of arranged nodes including spacing, with nodes of blocks in place.
+  fn placed(&self) -> Vec<(NodeId, Rect)> {
+    let mut placed = Vec::with_capacity(self.nodes.len());
+    for (idx, node) in self.nodes.iter().enumerate() {
+      let rect = Rect::from_min_size(pos2(node.x, node.y), vec2(node.width, node.height));
+      match node.id {
+        Some(id) => placed.push((id, rect)),
+        None => placed.extend(
+          self.blocks[&idx]
+            .iter()
+            .map(|&(id, block_rect)| (id, block_rect.translate(rect.min.to_vec2()))),
+        ),
       }
+    }
+    placed
+  }
 
-      child_offsets.push(shift);
-      current_offset = shift;
+  /// First walk: bottom-up traversal computing preliminary x coordinates and modifiers.
+  fn first_walk(&mut self) {
+    // Frames are `(node, next child, lowest y list)`.
+    let mut stack: Vec<(usize, usize, Option<usize>)> = vec![(0, 0, None)];
 
-      // Merge the new subtree's contour into the merged contour
-      for (depth, (l, r)) in child_node.contour.iter().enumerate() {
-        let shifted_l = l + shift;
-        let shifted_r = r + shift;
+    while let Some(&(node, child, _)) = stack.last() {
+      if let Some(&next) = self.nodes[node].children.get(child) {
+        stack.push((next, 0, None));
+        continue;
+      }
+
+      if !self.nodes[node].children.is_empty() {
+        self.position_root(node);
+      }
+      self.set_extremes(node);
+      stack.pop();
 
-        if depth < merged_contour.len() {
-          // Extend existing level
-          let (exist_l, exist_r) = merged_contour[depth];
-          merged_contour[depth] = (exist_l.min(shifted_l), exist_r.max(shifted_r));
+      // Merge finished subtree into its left siblings.
+      if let Some((parent, child, lowest)) = stack.last_mut() {
+        let parent = *parent;
+        let i = *child;
+        let children = &self.nodes[parent].children;
+        *lowest = if i == 0 {
+          let low_y = self.nodes[self.nodes[children[0]].extreme_left].bottom();
+          Some(self.update_lowest(low_y, 0, None))
         } else {
-          // Add new level
-          merged_contour.push((shifted_l, shifted_r));
+          let low_y = self.nodes[self.nodes[children[i]].extreme_right].bottom();
+          let lowest = self.separate(parent, i, *lowest);
+          Some(self.update_lowest(low_y, i, lowest))
+        };
+        *child += 1;
+      }
+    }
+  }
+
+  /// Second walk: top-down traversal computing final x coordinates.
+  fn second_walk(&mut self) {
+    let mut stack = vec![(0, 0.0)];
+
+    while let Some((node, modsum)) = stack.pop() {
+      let modsum = modsum + self.nodes[node].modifier;
+      self.nodes[node].x = self.nodes[node].prelim + modsum;
+      self.add_child_spacing(node);
+
+      for &child in &self.nodes[node].children {
+        stack.push((child, modsum));
+      }
+    }
+  }
+
+  fn set_extremes(&mut self, node: usize) {
+    let children = &self.nodes[node].children;
+    let (extreme_left, modsum_left, extreme_right, modsum_right) =
+      match (children.first(), children.last()) {
+        (Some(&first), Some(&last)) => (
+          self.nodes[first].extreme_left,
+          self.nodes[first].modsum_left,
+          self.nodes[last].extreme_right,
+          self.nodes[last].modsum_right,
+        ),
+        _ => (node, 0.0, node, 0.0),
+      };
+
+    let node = &mut self.nodes[node];
+    node.extreme_left = extreme_left;
+    node.modsum_left = modsum_left;
+    node.extreme_right = extreme_right;
+    node.modsum_right = modsum_right;
+  }
+
+  /// Pushes subtree of the `i`-th child of `node` right until it doesn't overlap its left siblings.
+  fn separate(&mut self, node: usize, i: usize, mut lowest: Option<usize>) -> Option<usize> {
+    // Right contour node of left siblings and its sum of modifiers.
+    let mut sr = Some(self.nodes[node].children[i - 1]);
+    let mut modsum_sr = sr.map_or(0.0, |sr| self.nodes[sr].modifier);
+
+    // Left contour node of the current subtree and its sum of modifiers.
+    let mut cl = Some(self.nodes[node].children[i]);
+    let mut modsum_cl = cl.map_or(0.0, |cl| self.nodes[cl].modifier);
+
+    // Only the first pair of contour nodes are siblings.
+    let mut gap = 0.0;
+
+    while let (Some(r), Some(l)) = (sr, cl) {
+      self.contour_steps += 1;
+      if let Some(entry) = lowest
+        && self.nodes[r].bottom() > self.lowest[entry].low_y
+      {
+        lowest = self.lowest[entry].next;
+      }
+
+      // How far to the left of the right side of `r` is the left side of `l`?
+      let dist = (modsum_sr + self.nodes[r].prelim + self.nodes[r].width + gap)
+        - (modsum_cl + self.nodes[l].prelim);
+      gap = self.subtree_spacing;
+      if dist > 0.0 {
+        modsum_cl += dist;
+        let si = lowest.map_or(0, |entry| self.lowest[entry].index);
+        self.move_subtree(node, i, si, dist);
+      }
+
+      // Advance the highest node(s) and sum(s) of modifiers.
+      let sy = self.nodes[r].bottom();
+      let cy = self.nodes[l].bottom();
+      if sy <= cy {
+        sr = self.next_right_contour(r);
+        if let Some(r) = sr {
+          modsum_sr += self.nodes[r].modifier;
+        }
+      }
+      if sy >= cy {
+        cl = self.next_left_contour(l);
+        if let Some(l) = cl {
+          modsum_cl += self.nodes[l].modifier;
         }
       }
     }
+
+    // Set threads and update extreme nodes.
+    match (sr, cl) {
+      // The current subtree is taller than its left siblings.
+      (None, Some(l)) => self.set_left_thread(node, i, l, modsum_cl),
+      // The left siblings are taller than the current subtree.
+      (Some(r), None) => self.set_right_thread(node, i, r, modsum_sr),
+      _ => {}
+    }
+
+    lowest
   }
 
-  // 3. Calculate parent node position
-  // Parent should be centered above its children
-  // The center of children group is at current_offset / 2.0
-  let children_center = current_offset / 2.0;
+  fn move_subtree(&mut self, node: usize, i: usize, si: usize, dist: f32) {
+    let child = self.nodes[node].children[i];
+    let child = &mut self.nodes[child];
+    child.modifier += dist;
+    child.modsum_left += dist;
+    child.modsum_right += dist;
+    self.distribute_extra(node, i, si, dist);
+  }
 
-  // 4. Generate final contour for this node
-  // Layer 0: the node itself
-  // Layer 1+: children group, shifted so that children center aligns with parent center (0.0)
-  let shift_children_left = -children_center;
-  let mut final_contour = Vec::new();
-  final_contour.push((-w / 2.0, w / 2.0)); // Layer 0: parent node
+  /// Spreads the shift of the `i`-th child among siblings between it and the `si`-th child.
+  #[allow(clippy::cast_precision_loss)]
+  fn distribute_extra(&mut self, node: usize, i: usize, si: usize, dist: f32) {
+    if si + 1 == i {
+      return;
+    }
 
-  // Layer 1+: children
-  for (l, r) in merged_contour {
-    final_contour.push((l + shift_children_left, r + shift_children_left));
+    let nr = (i - si) as f32;
+    let first = self.nodes[node].children[si + 1];
+    let current = self.nodes[node].children[i];
+    self.nodes[first].shift += dist / nr;
+    self.nodes[current].shift -= dist / nr;
+    self.nodes[current].change -= dist - dist / nr;
   }
 
-  // 5. Update child offsets in LayoutState
-  for (i, child_id) in children.iter().enumerate() {
-    if let Some(node) = state.nodes.get_mut(child_id) {
-      // Final X offset = offset within children group + group's offset relative to parent
-      node.offset_x = child_offsets[i] + shift_children_left;
+  /// Applies accumulated shifts and changes to modifiers of children.
+  fn add_child_spacing(&mut self, node: usize) {
+    let mut d = 0.0;
+    let mut modsum_delta = 0.0;
+    for idx in 0..self.nodes[node].children.len() {
+      let child = self.nodes[node].children[idx];
+      let child = &mut self.nodes[child];
+      d += child.shift;
+      modsum_delta += d + child.change;
+      child.modifier += modsum_delta;
     }
   }
 
-  // 6. Store this node's layout info
-  state.nodes.insert(
-    node_id,
-    LayoutNode {
-      offset_x: 0.0, // Root nodes have 0 offset (will be set in second_walk)
-      contour: final_contour,
-      width: w,
-      height: h,
-    },
-  );
-}
+  fn next_left_contour(&self, node: usize) -> Option<usize> {
+    let node = &self.nodes[node];
+    node.children.first().copied().or(node.thread_left)
+  }
 
-/// Second walk: top-down traversal to calculate absolute positions.
-fn second_walk<P: NodeDimensionProvider>(
-  node_id: NodeId,
-  absolute_x: f32,
-  absolute_y: f32,
-  state: &LayoutState,
-  positions: &mut HashMap<NodeId, Pos2>,
-  provider: &P,
-  config: &LayoutConfig,
-) {
-  let layout_node = state.nodes.get(&node_id).unwrap();
+  fn next_right_contour(&self, node: usize) -> Option<usize> {
+    let node = &self.nodes[node];
+    node.children.last().copied().or(node.thread_right)
+  }
+
+  fn set_left_thread(&mut self, node: usize, i: usize, cl: usize, modsum_cl: f32) {
+    let first = self.nodes[node].children[0];
+    let current = self.nodes[node].children[i];
+    let li = self.nodes[first].extreme_left;
+    self.nodes[li].thread_left = Some(cl);
 
-  // Calculate absolute position (top-left corner)
-  // absolute_x is the parent's center X
-  // layout_node.offset_x is the offset from parent's center
-  let center_x = absolute_x + layout_node.offset_x;
-  let top_left_x = center_x - layout_node.width / 2.0;
+    // Change modifier so that the sum of modifiers after following the thread is correct.
+    let diff = (modsum_cl - self.nodes[cl].modifier) - self.nodes[first].modsum_left;
+    self.nodes[li].modifier += diff;
+    // Change preliminary x so that the node does not move.
+    self.nodes[li].prelim -= diff;
+
+    self.nodes[first].extreme_left = self.nodes[current].extreme_left;
+    self.nodes[first].modsum_left = self.nodes[current].modsum_left;
+  }
+
+  fn set_right_thread(&mut self, node: usize, i: usize, sr: usize, modsum_sr: f32) {
+    let previous = self.nodes[node].children[i - 1];
+    let current = self.nodes[node].children[i];
+    let ri = self.nodes[current].extreme_right;
+    self.nodes[ri].thread_right = Some(sr);
+
+    let diff = (modsum_sr - self.nodes[sr].modifier) - self.nodes[current].modsum_right;
+    self.nodes[ri].modifier += diff;
+    self.nodes[ri].prelim -= diff;
+
+    self.nodes[current].extreme_right = self.nodes[previous].extreme_right;
+    self.nodes[current].modsum_right = self.nodes[previous].modsum_right;
+  }
 
-  positions.insert(node_id, pos2(top_left_x, absolute_y));
+  /// Places the node over its children as set by [`ParentAlignment`].
+  ///
+  /// Parents of wrapped children are centered over them, where wires pass between the rows.
+  fn position_root(&mut self, node: usize) {
+    let children = &self.nodes[node].children;
+    let first = &self.nodes[children[0]];
+    let last = &self.nodes[children[children.len() - 1]];
+    let alignment = match children.as_slice() {
+      [block] if self.nodes[*block].id.is_none() => ParentAlignment::Center,
+      _ => self.parent_alignment,
+    };
 
-  let children = provider.get_children(node_id);
-  let next_y = absolute_y + layout_node.height + config.vertical_spacing;
+    let first_center = first.prelim + first.modifier + first.width / 2.0;
+    let last_center = last.prelim + last.modifier + last.width / 2.0;
+    let half_width = self.nodes[node].width / 2.0;
 
-  for child_id in children {
-    second_walk(child_id, center_x, next_y, state, positions, provider, config);
+    self.nodes[node].prelim = match alignment {
+      ParentAlignment::Center => f32::midpoint(first_center, last_center) - half_width,
+      ParentAlignment::FirstChild => first_center - half_width,
+      ParentAlignment::LastChild => last_center - half_width,
+      ParentAlignment::Start => first.prelim + first.modifier,
+    };
+  }
+
+  /// Prepends the `i`-th child to the lowest y list, dropping siblings hidden by it.
+  fn update_lowest(&mut self, low_y: f32, index: usize, mut lowest: Option<usize>) -> usize {
+    while let Some(entry) = lowest
+      && low_y >= self.lowest[entry].low_y
+    {
+      lowest = self.lowest[entry].next;
+    }
+
+    self.lowest.push(LowestY { low_y, index, next: lowest });
+    self.lowest.len() - 1
   }
 }
 
 /// Performs hierarchical layout on a tree-like graph.
 ///
-/// This function arranges nodes in a top-to-bottom tree structure using
-/// a compact contour-based layout algorithm (similar to ELK Mr.Tree).
-/// Nodes are organized into levels based on their distance from root nodes.
+/// This function arranges nodes in a tidy tree structure using
+/// the Buchheim-Walker algorithm, extended for nodes of variable sizes.
+/// Each parent is centered above its children. Runs in linear time.
+/// Children are placed right below their parent, levels follow [`LayoutConfig::orientation`].
+/// A node with several parents is placed under the first one found.
+///
+/// With [`LayoutAlgorithm::Layered`] nodes are arranged in layers instead,
+/// which suits directed acyclic graphs with shared children.
+/// With [`LayoutAlgorithm::Radial`] each root is placed in the center of a disc
+/// and its descendants on rings around it, which suits wide and shallow trees.
 ///
 /// # Arguments
 ///
@@ -258,80 +988,434 @@ fn second_walk<P: NodeDimensionProvider>(
 ///
 /// # Returns
 ///
-/// A map from node ID to its calculated position (top-left corner).
+/// [`LayoutResult`] with position of each node (top-left corner), wires that close cycles,
+/// depths and bounds of nodes and suggested paths of wires.
 ///
-/// # Panics
+/// # Determinism
 ///
-/// This function does not panic, but may produce unexpected layouts if the graph
-/// contains cycles or if node sizes are invalid.
+/// The same graph with the same sizes and config is always laid out the same way.
+/// Roots are taken in the order of their [`NodeId`]s and children in the order
+/// of [`Treeize::sorted_wires`], by output pin and then by [`NodeId`],
+/// regardless of the order in which wires were connected.
+/// Maps in [`LayoutResult`] have no particular iteration order, but their contents are fixed.
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let build = |order: [usize; 4]| {
+///   let mut treeize = Treeize::new();
+///   let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   let children = [(); 4].map(|()| treeize.insert_node(egui::pos2(0.0, 0.0), ()));
+///   for idx in order {
+///     let child = children[idx];
+///     treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+///   }
+///   treeize
+/// };
+///
+/// let layout = |treeize: &Treeize<()>| {
+///   layout_tree(treeize, LayoutConfig::default(), |_| true, |_| true, None).positions
+/// };
+/// assert_eq!(layout(&build([0, 1, 2, 3])), layout(&build([3, 1, 0, 2])));
+/// ```
+///
+/// # Variable sizes
+///
+/// Each parent is centered over its first and last child, however uneven their subtrees are.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let leaf = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let wide = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: leaf, input: 0 });
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: wide, input: 0 });
+/// for _ in 0..5 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: wide, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+///
+/// let sizes = treeize.node_ids().map(|(id, _)| (id, egui::vec2(80.0, 40.0))).collect();
+/// let positions = layout_tree(&treeize, LayoutConfig::default(), |_| true, |_| true, Some(&sizes))
+///   .positions;
+/// assert_eq!(positions[&root].x, f32::midpoint(positions[&leaf].x, positions[&wide].x));
+/// ```
+///
+/// Nodes are kept apart by their actual extents rather than by depth,
+/// so a tall node doesn't overlap deeper nodes of the neighboring subtree.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use egui::{Rect, vec2};
+/// # use egui_treeize::{InPinId, NodeId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let overlaps = |treeize: &Treeize<()>, sizes: &HashMap<NodeId, egui::Vec2>| {
+///   let config = LayoutConfig::default();
+///   let positions = layout_tree(treeize, config, |_| true, |_| true, Some(sizes)).positions;
+///   let rects =
+///     positions.iter().map(|(id, &pos)| Rect::from_min_size(pos, sizes[id])).collect::<Vec<_>>();
+///   let apart = |a: &Rect, b: &Rect| !a.intersects(b.shrink(0.01));
+///   rects.iter().enumerate().any(|(idx, a)| !rects[idx + 1..].iter().all(|b| apart(a, b)))
+/// };
+///
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let tall = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let short = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let wide = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: tall, input: 0 });
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: short, input: 0 });
+/// treeize.connect(OutPinId { node: short, output: 0 }, InPinId { node: wide, input: 0 });
+///
+/// let sizes = HashMap::from([
+///   (root, vec2(100.0, 40.0)),
+///   (tall, vec2(100.0, 1000.0)),
+///   (short, vec2(100.0, 10.0)),
+///   (wide, vec2(1000.0, 40.0)),
+/// ]);
+/// assert!(!overlaps(&treeize, &sizes));
+///
+/// // Random trees with random node sizes.
+/// let mut seed = 7u32;
+/// let mut random = move |max: u32| {
+///   seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+///   (seed >> 8) % max
+/// };
+/// for _ in 0..20 {
+///   let mut treeize = Treeize::new();
+///   let mut sizes = HashMap::new();
+///   let mut nodes = Vec::new();
+///   for idx in 0..60 {
+///     let node = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///     #[allow(clippy::cast_precision_loss)]
+///     sizes.insert(node, vec2((20 + random(300)) as f32, (20 + random(300)) as f32));
+///     if idx > 0 {
+///       let parent = nodes[random(idx) as usize];
+///       treeize.connect(OutPinId { node: parent, output: 0 }, InPinId { node, input: 0 });
+///     }
+///     nodes.push(node);
+///   }
+///   assert!(!overlaps(&treeize, &sizes));
+/// }
+/// ```
+///
+/// Layout takes time linear in the number of nodes, even when subtrees are merged
+/// along long contours, like in a comb of leaves hanging off a long chain.
+/// Debug builds check that subtrees are separated in a number of steps
+/// proportional to the number of nodes.
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let mut spine = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// for _ in 0..50_000 {
+///   let leaf = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   let next = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: spine, output: 0 }, InPinId { node: leaf, input: 0 });
+///   treeize.connect(OutPinId { node: spine, output: 0 }, InPinId { node: next, input: 0 });
+///   spine = next;
+/// }
+///
+/// let result = layout_tree(&treeize, LayoutConfig::default(), |_| true, |_| true, None);
+/// assert_eq!(result.positions.len(), 100_001);
+/// ```
+///
+/// # Cycles
+///
+/// Nodes are arranged along a spanning forest found by depth-first search,
+/// so cycles can't make the layout hang and deep trees can't overflow the stack.
+/// Nodes reachable only through cycles become roots of their own trees.
+/// Wires that close cycles are left out and reported in [`LayoutResult::back_edges`].
+///
+/// # Collapsed nodes
+///
+/// With [`LayoutConfig::fold_collapsed`] descendants of collapsed nodes take no space.
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let folded = treeize.insert_node_collapsed(egui::pos2(0.0, 0.0), ());
+/// let other = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: folded, input: 0 });
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: other, input: 0 });
+/// for _ in 0..5 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: folded, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+///
+/// let config = LayoutConfig { fold_collapsed: true, ..Default::default() };
+/// let positions = layout_tree(&treeize, config, |_| true, |_| true, None).positions;
+///
+/// // Siblings are as close as if `folded` had no children.
+/// let distance = (positions[&other].x - positions[&folded].x).abs();
+/// assert_eq!(distance, config.horizontal_spacing * 2.0);
+/// ```
 #[allow(clippy::implicit_hasher)]
 pub fn layout_tree<T>(
   treeize: &Treeize<T>,
   config: LayoutConfig,
-  mut has_output: impl FnMut(NodeId) -> bool,
-  mut has_input: impl FnMut(NodeId) -> bool,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
   node_sizes: Option<&HashMap<NodeId, Vec2>>,
-) -> HashMap<NodeId, Pos2> {
-  let mut positions = HashMap::new();
+) -> LayoutResult {
+  layout_tree_constrained(treeize, config, has_output, has_input, node_sizes, &HashMap::new())
+}
+
+/// Performs layout like [`layout_tree`] while satisfying per-node constraints.
+///
+/// Nodes are arranged as usual first. Then, from roots to leaves, each constrained node
+/// is moved where its [`LayoutConstraint`] puts it, together with its subtree.
+/// Everything else is arranged around them: parts of trees that are not under
+/// a constrained node are shifted across levels, each as a whole, by the smallest distance
+/// that keeps them [`LayoutConfig::horizontal_spacing`] away from constrained nodes
+/// and from each other. Constrained nodes may overlap each other if constraints say so.
+/// Placed nodes are indexed across levels, so moving a tree out of the way takes time
+/// proportional to the number of its nodes times the number of placed nodes next to it,
+/// rather than all of them.
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, LayoutConstraint, layout_tree_constrained};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let pinned = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let child = treeize.insert_node(egui::pos2(50.0, 300.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: pinned, input: 0 });
+/// treeize.connect(OutPinId { node: pinned, output: 0 }, InPinId { node: child, input: 0 });
+///
+/// let constraints = HashMap::from([
+///   (pinned, LayoutConstraint::Pinned(egui::pos2(0.0, 0.0))),
+///   (child, LayoutConstraint::KeepParentOffset),
+/// ]);
+/// let config = LayoutConfig::default();
+/// let positions =
+///   layout_tree_constrained(&treeize, config, |_| true, |_| true, None, &constraints).positions;
+///
+/// assert_eq!(positions[&pinned], egui::pos2(0.0, 0.0));
+/// assert_eq!(positions[&child], egui::pos2(50.0, 300.0));
+/// // The root is moved out of the way of the pinned node.
+/// assert!(positions[&root].x >= config.horizontal_spacing * 2.0);
+/// ```
+#[allow(clippy::implicit_hasher)]
+pub fn layout_tree_constrained<T>(
+  treeize: &Treeize<T>,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+  constraints: &HashMap<NodeId, LayoutConstraint>,
+) -> LayoutResult {
+  layout_tree_with(treeize, &config, config, has_output, has_input, node_sizes, constraints)
+}
 
-  // Build adapter
-  let adapter = TreeizeAdapter::new(treeize, node_sizes, &mut has_output, &mut has_input, config);
+/// Performs layout like [`layout_tree_constrained`] with a custom [`TreeLayout`].
+///
+/// `layout` arranges nodes of the [`LayoutGraph`], in place of the built-in algorithms.
+/// The rest is still driven by `config`: nodes are folded by [`LayoutConfig::fold_collapsed`],
+/// unmeasured nodes get sizes from the spacing and constraints move nodes
+/// along [`LayoutConfig::orientation`].
+/// Passing the `config` itself as `layout` is the same as [`layout_tree_constrained`].
+///
+/// See [`TreeLayout`] for an example.
+#[allow(clippy::implicit_hasher)]
+pub fn layout_tree_with<T, L>(
+  treeize: &Treeize<T>,
+  layout: &L,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+  constraints: &HashMap<NodeId, LayoutConstraint>,
+) -> LayoutResult
+where
+  L: TreeLayout + ?Sized,
+{
+  arrange(treeize, layout, config, has_output, has_input, node_sizes, |_, _| 1.0, constraints)
+}
 
-  // Find root nodes (nodes with no incoming edges)
-  let mut has_incoming: HashMap<NodeId, bool> = HashMap::new();
-  for (out_pin, in_pin) in treeize.wires() {
-    let to_node = in_pin.node;
-    if has_output(out_pin.node) && has_input(to_node) {
-      has_incoming.insert(to_node, true);
-    }
+/// Performs layout like [`layout_tree`] with children placed by weights of their wires.
+///
+/// [`LayoutAlgorithm::Tidy`] places each child `weight` times [`LayoutConfig::vertical_spacing`]
+/// away from its parent, so wires with weights like time, cost or distance
+/// get proportionally long. Weight one is the usual spacing, negative weights count as zero.
+/// Siblings are still packed next to each other as closely as their subtrees allow.
+/// Weights are ignored with [`LevelAlignment::Aligned`], for children wrapped by
+/// [`LayoutConfig::sibling_wrapping`] and by other [`LayoutAlgorithm`]s.
+///
+/// Viewers supply weights with [`TreeizeViewer::layout_weight`].
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree_weighted};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let near = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let far = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: near, input: 0 });
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: far, input: 0 });
+///
+/// let config = LayoutConfig::default();
+/// let weight = |_, in_pin: InPinId| if in_pin.node == far { 3.0 } else { 1.0 };
+/// let positions =
+///   layout_tree_weighted(&treeize, config, |_| true, |_| true, None, weight).positions;
+///
+/// assert_eq!(positions[&far].y - positions[&near].y, config.vertical_spacing * 2.0);
+/// ```
+#[allow(clippy::implicit_hasher)]
+pub fn layout_tree_weighted<T>(
+  treeize: &Treeize<T>,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+  weight: impl FnMut(OutPinId, InPinId) -> f32,
+) -> LayoutResult {
+  arrange(treeize, &config, config, has_output, has_input, node_sizes, weight, &HashMap::new())
+}
+
+/// Performs layout with all inputs, see [`layout_tree_with`] and [`layout_tree_weighted`].
+#[allow(clippy::too_many_arguments)]
+fn arrange<T, L>(
+  treeize: &Treeize<T>,
+  layout: &L,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+  weight: impl FnMut(OutPinId, InPinId) -> f32,
+  constraints: &HashMap<NodeId, LayoutConstraint>,
+) -> LayoutResult
+where
+  L: TreeLayout + ?Sized,
+{
+  let folded = folded_nodes(treeize, &config);
+  let graph =
+    LayoutGraph::new(treeize, node_sizes, has_output, has_input, weight, &folded, &config);
+
+  let mut positions = layout.layout(&graph);
+  if !constraints.is_empty() {
+    let oriented = graph.oriented(config.orientation);
+    let forest = SpanningForest::new(graph.nodes(), &oriented);
+    constraint::apply_constraints(&mut positions, &forest, constraints, &config, |id| {
+      treeize.nodes[id.0].pos
+    });
   }
+  carry_folded(&mut positions, &folded, treeize);
 
-  let root_nodes: Vec<NodeId> = treeize
-    .node_ids()
-    .map(|(node_id, _)| node_id)
-    .filter(|node_id| !has_incoming.get(node_id).copied().unwrap_or(false))
-    .collect();
+  LayoutResult::new(&graph, treeize, positions, &config)
+}
 
-  if root_nodes.is_empty() {
-    // No root nodes found, treat all nodes as disconnected
-    for (node_id, _) in treeize.node_ids() {
-      positions.insert(node_id, config.start_pos);
+impl TreeLayout for LayoutConfig {
+  fn layout(&self, graph: &LayoutGraph<'_>) -> HashMap<NodeId, Pos2> {
+    let oriented = graph.oriented(self.orientation);
+    let forest = SpanningForest::new(graph.nodes(), &oriented);
+
+    let mut positions = match self.algorithm {
+      LayoutAlgorithm::Tidy => tidy_layout(&forest.roots, &forest, self),
+      LayoutAlgorithm::Layered => {
+        layered::layered_layout(graph.nodes(), &oriented, graph.back_edges(), self)
+      }
+      LayoutAlgorithm::Radial => radial::radial_layout(&forest.roots, &forest, self),
+      LayoutAlgorithm::Force => force::force_layout(graph, self),
+    };
+    packing::pack_forest(&mut positions, &forest, self);
+    positions
+  }
+}
+
+/// Returns nodes folded into collapsed nodes if the config asks to.
+fn folded_nodes<T>(
+  treeize: &Treeize<T>,
+  config: &LayoutConfig,
+) -> egui::ahash::HashMap<NodeId, NodeId> {
+  if config.fold_collapsed { treeize.folded_nodes() } else { egui::ahash::HashMap::default() }
+}
+
+/// Moves folded nodes by the same offset as the collapsed nodes that hide them.
+fn carry_folded<T>(
+  positions: &mut HashMap<NodeId, Pos2>,
+  folded: &egui::ahash::HashMap<NodeId, NodeId>,
+  treeize: &Treeize<T>,
+) {
+  for (&node, owner) in folded {
+    if let Some(owner_pos) = positions.get(owner) {
+      let offset = *owner_pos - treeize.nodes[owner.0].pos;
+      positions.insert(node, treeize.nodes[node.0].pos + offset);
     }
-    return positions;
   }
+}
 
-  // Calculate layout for each root node's tree
-  let mut layout_state = LayoutState::new();
+/// Arranges tree of each root with [`TidyTree`], trees are placed next to each other.
+fn tidy_layout<P: NodeDimensionProvider>(
+  root_nodes: &[NodeId],
+  provider: &P,
+  config: &LayoutConfig,
+) -> HashMap<NodeId, Pos2> {
+  let mut positions = HashMap::new();
+  let mut visited = HashSet::new();
   let mut root_x_offset = 0.0;
 
-  for root_id in &root_nodes {
-    // First walk: calculate contours and offsets
-    first_walk(*root_id, &adapter, &config, &mut layout_state);
-
-    // Calculate the width of this root's tree
-    let root_node = layout_state.nodes.get(root_id).unwrap();
-    let tree_width = root_node.contour.iter().map(|(l, r)| r - l).fold(0.0f32, f32::max);
-
-    // Second walk: calculate absolute positions
-    // Start at config.start_pos.x + root_x_offset (centered)
-    let root_center_x = config.start_pos.x + root_x_offset + tree_width / 2.0;
-    second_walk(
-      *root_id,
-      root_center_x,
-      config.start_pos.y,
-      &layout_state,
-      &mut positions,
-      &adapter,
-      &config,
-    );
+  let mut trees = Vec::new();
+  for root_id in root_nodes {
+    if !visited.contains(root_id) {
+      trees.push(TidyTree::new(*root_id, provider, config, &mut visited));
+    }
+  }
 
-    // Update offset for next root
-    root_x_offset += tree_width + config.horizontal_spacing;
+  if config.level_alignment == LevelAlignment::Aligned {
+    // Each depth starts after the largest node of the previous depth in any tree.
+    // Blocks of wrapped children extend below their level.
+    let mut levels = Vec::new();
+    for node in trees.iter().flat_map(|tree| &tree.nodes) {
+      if levels.len() <= node.depth {
+        levels.resize(node.depth + 1, 0.0);
+      }
+      if node.id.is_some() {
+        levels[node.depth] = f32::max(levels[node.depth], node.height);
+      }
+    }
+    let mut level_y = 0.0;
+    for height in &mut levels {
+      (*height, level_y) = (level_y, level_y + *height);
+    }
+
+    for node in trees.iter_mut().flat_map(|tree| &mut tree.nodes) {
+      node.y = levels[node.depth];
+    }
   }
 
-  // Handle disconnected nodes (nodes not reachable from any root)
-  for (node_id, _) in treeize.node_ids() {
-    positions.entry(node_id).or_insert(config.start_pos);
+  for mut tree in trees {
+    tree.layout();
+    let placed = tree.placed();
+
+    // Width of this root's tree, including spacing on both sides.
+    let (min_x, max_x) =
+      placed.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (_, rect)| {
+        (min.min(rect.min.x), max.max(rect.max.x))
+      });
+
+    for (id, rect) in placed {
+      let size = rect.size() - vec2(config.horizontal_spacing, config.vertical_spacing);
+      let pos = pos2(
+        config.start_pos.x + root_x_offset + rect.min.x - min_x + config.horizontal_spacing / 2.0,
+        config.start_pos.y + rect.min.y,
+      );
+      positions.insert(id, config.orientation.orient(config.start_pos, pos, size));
+    }
+
+    // Update offset for next root
+    root_x_offset += max_x - min_x + config.subtree_spacing;
   }
 
   positions
@@ -356,6 +1440,8 @@ where
 
 /// Convenience function that performs layout and applies it in one step.
 ///
+/// Returns the applied [`LayoutResult`].
+///
 /// # Arguments
 ///
 /// * `treeize` - The tree graph to layout and update
@@ -368,7 +1454,7 @@ where
 /// # Example
 ///
 /// ```rs
-/// use egui_treeize::{Treeize, layout::{LayoutConfig, layout_and_apply}};
+/// use egui_treeize::{Treeize, layout::{ForceConfig, ForestPacking, LayoutAlgorithm, LayoutConfig, LayoutOrientation, LevelAlignment, ParentAlignment, SiblingWrapping, layout_and_apply}};
 /// use std::collections::HashMap;
 ///
 /// struct MyNode;
@@ -376,8 +1462,20 @@ where
 /// let mut treeize = Treeize::<MyNode>::new();
 /// let config = LayoutConfig {
 ///     horizontal_spacing: 200.0,
+///     subtree_spacing: 0.0,
 ///     vertical_spacing: 150.0,
 ///     start_pos: egui::pos2(0.0, 0.0),
+///     orientation: LayoutOrientation::LeftToRight,
+///     algorithm: LayoutAlgorithm::Tidy,
+///     parent_alignment: ParentAlignment::Center,
+///     level_alignment: LevelAlignment::Compact,
+///     forest_packing: ForestPacking::Row,
+///     packing_aspect_ratio: 16.0 / 9.0,
+///     singleton_grid: false,
+///     fold_collapsed: false,
+///     force: ForceConfig::default(),
+///     sibling_wrapping: SiblingWrapping::Off,
+///     max_siblings: 16,
 /// };
 ///
 /// // Optional: provide node sizes
@@ -409,15 +1507,22 @@ pub fn layout_and_apply<T>(
   has_output: impl FnMut(NodeId) -> bool,
   has_input: impl FnMut(NodeId) -> bool,
   node_sizes: Option<&HashMap<NodeId, Vec2>>,
-) {
-  let positions = layout_tree(treeize, config, has_output, has_input, node_sizes);
-  apply_layout(treeize, &positions);
+) -> LayoutResult {
+  let result = layout_tree(treeize, config, has_output, has_input, node_sizes);
+  apply_layout(treeize, &result.positions);
+  result
 }
 
 /// Convenience function that performs layout using a `TreeizeViewer`.
 ///
 /// This function automatically uses the viewer's `has_input` and `has_output` methods
-/// to determine node connectivity.
+/// to determine node connectivity, [`TreeizeViewer::layout_constraint`] to constrain
+/// positions of nodes as in [`layout_tree_constrained`] and [`TreeizeViewer::layout_weight`]
+/// to weigh wires as in [`layout_tree_weighted`]. Returns the applied [`LayoutResult`].
+///
+/// Node sizes are those measured when the widget drew the nodes last.
+/// Nodes that were not drawn yet, e.g. before the first frame or without a widget at all,
+/// take sizes from [`TreeizeViewer::estimate_size`].
 ///
 /// # Arguments
 ///
@@ -454,46 +1559,256 @@ pub fn layout_with_viewer<T, V>(
   config: LayoutConfig,
   ctx: &Context,
   treeize_id: Id,
-) where
+) -> LayoutResult
+where
   V: TreeizeViewer<T>,
 {
-  // Read node sizes from NodeState stored in Context
-  let mut node_sizes_map: HashMap<NodeId, Vec2> = HashMap::new();
+  layout_with_viewer_using(treeize, viewer, &config, config, ctx, treeize_id)
+}
 
-  for (node_id, _) in treeize.node_ids() {
-    let node_state_id = treeize_id.with(("treeize-node", node_id));
-    if let Some(node_data) = NodeState::pick_data(ctx, node_state_id) {
-      node_sizes_map.insert(node_id, node_data.size);
-    }
+/// Performs layout like [`layout_with_viewer`] with a custom [`TreeLayout`].
+///
+/// See [`layout_tree_with`] for how `layout` and `config` work together.
+pub fn layout_with_viewer_using<T, V, L>(
+  treeize: &mut Treeize<T>,
+  viewer: &mut V,
+  layout: &L,
+  config: LayoutConfig,
+  ctx: &Context,
+  treeize_id: Id,
+) -> LayoutResult
+where
+  V: TreeizeViewer<T>,
+  L: TreeLayout + ?Sized,
+{
+  let inputs = ViewerInputs::new(treeize, viewer, ctx, treeize_id);
+  let result = arrange(
+    treeize,
+    layout,
+    config,
+    |node_id| inputs.has_output(node_id),
+    |node_id| inputs.has_input(node_id),
+    inputs.node_sizes(),
+    |out_pin, in_pin| inputs.weight(out_pin, in_pin),
+    &inputs.constraints,
+  );
+  apply_layout(treeize, &result.positions);
+  result
+}
+
+/// Re-arranges the subtree of `node` and applies it, leaving unrelated nodes in place.
+///
+/// The subtree is arranged with the tidy tree algorithm while `node` itself stays where it is.
+/// Then siblings of the subtree and of each of its ancestors are shifted to keep their clearance
+/// to it, and ancestors keep their offset from the middle of their children.
+/// Nodes of other trees are never moved.
+/// Space freed by removed nodes is kept, [`layout_tree`] compacts it.
+/// Nothing is moved if `node` is hidden by [`LayoutConfig::fold_collapsed`].
+///
+/// Other [`LayoutAlgorithm`]s can't arrange a subtree on its own,
+/// with them the whole graph is laid out with [`layout_and_apply`].
+/// So is it when [`LayoutConfig::sibling_wrapping`] is on.
+///
+/// Arguments are the same as for [`layout_tree`].
+///
+/// # Returns
+///
+/// [`LayoutResult`] with positions of the moved nodes only.
+/// The rest of it describes the whole graph after the move.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_and_apply, relayout_subtree};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let left = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let right = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: left, input: 0 });
+/// let cousin = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: right, input: 0 });
+/// treeize.connect(OutPinId { node: right, output: 0 }, InPinId { node: cousin, input: 0 });
+/// layout_and_apply(&mut treeize, LayoutConfig::default(), |_| true, |_| true, None);
+///
+/// // New children of `left` push `right` and its child away, `left` stays in place.
+/// let x = |treeize: &Treeize<()>, node| treeize.get_node_info(node).unwrap().pos.x;
+/// let (left_x, distance) = (x(&treeize, left), (x(&treeize, right) - x(&treeize, left)).abs());
+/// for _ in 0..3 {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: left, output: 0 }, InPinId { node: child, input: 0 });
+/// }
+/// let result =
+///   relayout_subtree(&mut treeize, left, LayoutConfig::default(), |_| true, |_| true, None);
+///
+/// assert_eq!(x(&treeize, left), left_x);
+/// assert!((x(&treeize, right) - x(&treeize, left)).abs() > distance);
+/// assert!(!result.positions.contains_key(&left));
+/// ```
+///
+/// Only the subtree, its siblings with their subtrees and its ancestors move:
+///
+/// ```
+/// # use egui_treeize::{InPinId, NodeId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_and_apply, relayout_subtree};
+/// let mut treeize = Treeize::new();
+/// let connect = |treeize: &mut Treeize<()>, parent: NodeId| {
+///   let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+///   treeize.connect(OutPinId { node: parent, output: 0 }, InPinId { node: child, input: 0 });
+///   child
+/// };
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let [left, middle, right] = [(); 3].map(|()| connect(&mut treeize, root));
+/// let [left_child, right_child] = [left, right].map(|parent| connect(&mut treeize, parent));
+/// let other_root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let other_child = connect(&mut treeize, other_root);
+/// layout_and_apply(&mut treeize, LayoutConfig::default(), |_| true, |_| true, None);
+///
+/// let added = [(); 4].map(|()| connect(&mut treeize, middle));
+/// let result =
+///   relayout_subtree(&mut treeize, middle, LayoutConfig::default(), |_| true, |_| true, None);
+///
+/// let may_move = [root, left, left_child, right, right_child];
+/// let may_move = may_move.into_iter().chain(added).collect::<Vec<_>>();
+/// assert!(result.positions.keys().all(|node| may_move.contains(node)));
+/// assert!(result.positions.contains_key(&left) && result.positions.contains_key(&right));
+/// assert!(!result.positions.contains_key(&other_root));
+/// assert!(!result.positions.contains_key(&other_child));
+/// ```
+#[allow(clippy::implicit_hasher)]
+pub fn relayout_subtree<T>(
+  treeize: &mut Treeize<T>,
+  node: NodeId,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+) -> LayoutResult {
+  relayout_weighted(treeize, node, config, has_output, has_input, node_sizes, |_, _| 1.0)
+}
+
+/// Re-arranges the subtree of `node` like [`relayout_subtree`], with weights of wires
+/// as in [`layout_tree_weighted`].
+fn relayout_weighted<T>(
+  treeize: &mut Treeize<T>,
+  node: NodeId,
+  config: LayoutConfig,
+  has_output: impl FnMut(NodeId) -> bool,
+  has_input: impl FnMut(NodeId) -> bool,
+  node_sizes: Option<&HashMap<NodeId, Vec2>>,
+  weight: impl FnMut(OutPinId, InPinId) -> f32,
+) -> LayoutResult {
+  if config.algorithm != LayoutAlgorithm::Tidy || config.sibling_wrapping != SiblingWrapping::Off {
+    let constraints = HashMap::new();
+    let result =
+      arrange(treeize, &config, config, has_output, has_input, node_sizes, weight, &constraints);
+    apply_layout(treeize, &result.positions);
+    return result;
   }
 
-  let node_sizes = if node_sizes_map.is_empty() { None } else { Some(&node_sizes_map) };
+  let folded = folded_nodes(treeize, &config);
+  if treeize.get_node_info(node).is_none() || folded.contains_key(&node) {
+    return LayoutResult::default();
+  }
+
+  let graph =
+    LayoutGraph::new(treeize, node_sizes, has_output, has_input, weight, &folded, &config);
+  let forest = graph.oriented_forest(config.orientation);
 
-  // First, collect all node data and compute has_input/has_output to avoid borrowing issues
-  let node_info: Vec<(NodeId, bool, bool)> = treeize
-    .node_ids()
-    .map(|(node_id, data)| {
-      let has_out = viewer.has_output(data);
-      let has_in = viewer.has_input(data);
-      (node_id, has_out, has_in)
-    })
-    .collect();
+  let mut positions =
+    subtree::relayout_subtree_positions(node, &forest, &config, |id| treeize.nodes[id.0].pos);
+  carry_folded(&mut positions, &folded, treeize);
+  let result = LayoutResult::new(&graph, treeize, positions, &config);
 
-  // Create a lookup map
-  let mut has_output_map: HashMap<NodeId, bool> = HashMap::new();
-  let mut has_input_map: HashMap<NodeId, bool> = HashMap::new();
+  apply_layout(treeize, &result.positions);
+  result
+}
 
-  for (node_id, has_out, has_in) in &node_info {
-    has_output_map.insert(*node_id, *has_out);
-    has_input_map.insert(*node_id, *has_in);
+/// Re-arranges the subtree of `node` using a `TreeizeViewer`.
+///
+/// Same as [`relayout_subtree`], with pins and sizes of nodes and weights of wires taken
+/// as in [`layout_with_viewer`].
+pub fn relayout_subtree_with_viewer<T, V>(
+  treeize: &mut Treeize<T>,
+  viewer: &mut V,
+  node: NodeId,
+  config: LayoutConfig,
+  ctx: &Context,
+  treeize_id: Id,
+) -> LayoutResult
+where
+  V: TreeizeViewer<T>,
+{
+  let inputs = ViewerInputs::new(treeize, viewer, ctx, treeize_id);
+  relayout_weighted(
+    treeize,
+    node,
+    config,
+    |node_id| inputs.has_output(node_id),
+    |node_id| inputs.has_input(node_id),
+    inputs.node_sizes(),
+    |out_pin, in_pin| inputs.weight(out_pin, in_pin),
+  )
+}
+
+/// Node sizes and pins collected from a `TreeizeViewer` for layout.
+struct ViewerInputs {
+  node_sizes: HashMap<NodeId, Vec2>,
+  /// Whether each node has outputs and inputs.
+  pins: HashMap<NodeId, (bool, bool)>,
+  constraints: HashMap<NodeId, LayoutConstraint>,
+  weights: HashMap<(OutPinId, InPinId), f32>,
+}
+
+impl ViewerInputs {
+  fn new<T, V>(treeize: &Treeize<T>, viewer: &mut V, ctx: &Context, treeize_id: Id) -> Self
+  where
+    V: TreeizeViewer<T>,
+  {
+    // Read node sizes from NodeState stored in Context, estimate the rest
+    let mut node_sizes = HashMap::new();
+    for (node_id, _) in treeize.node_ids() {
+      let node_state_id = treeize_id.with(("treeize-node", node_id));
+      let size = match NodeState::pick_data(ctx, node_state_id) {
+        Some(node_data) => Some(node_data.size),
+        None => viewer.estimate_size(node_id, treeize),
+      };
+      if let Some(size) = size {
+        node_sizes.insert(node_id, size);
+      }
+    }
+
+    let pins = treeize
+      .node_ids()
+      .map(|(node_id, data)| (node_id, (viewer.has_output(data), viewer.has_input(data))))
+      .collect();
+
+    let constraints = treeize
+      .node_ids()
+      .filter_map(|(node_id, _)| Some((node_id, viewer.layout_constraint(node_id, treeize)?)))
+      .collect();
+
+    let weights = treeize
+      .wires()
+      .map(|(out_pin, in_pin)| ((out_pin, in_pin), viewer.layout_weight(out_pin, in_pin, treeize)))
+      .collect();
+
+    ViewerInputs { node_sizes, pins, constraints, weights }
+  }
+
+  fn node_sizes(&self) -> Option<&HashMap<NodeId, Vec2>> {
+    if self.node_sizes.is_empty() { None } else { Some(&self.node_sizes) }
   }
 
-  // Create closures that use the maps
-  let has_output_fn =
-    |node_id: NodeId| -> bool { has_output_map.get(&node_id).copied().unwrap_or(false) };
+  fn has_output(&self, node_id: NodeId) -> bool {
+    self.pins.get(&node_id).is_some_and(|pins| pins.0)
+  }
 
-  let has_input_fn =
-    |node_id: NodeId| -> bool { has_input_map.get(&node_id).copied().unwrap_or(false) };
+  fn has_input(&self, node_id: NodeId) -> bool {
+    self.pins.get(&node_id).is_some_and(|pins| pins.1)
+  }
 
-  layout_and_apply(treeize, config, has_output_fn, has_input_fn, node_sizes);
+  fn weight(&self, out_pin: OutPinId, in_pin: InPinId) -> f32 {
+    self.weights.get(&(out_pin, in_pin)).copied().unwrap_or(1.0)
+  }
 }
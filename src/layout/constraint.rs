@@ -0,0 +1,235 @@
+//! Constraints on positions of single nodes.
+//!
+//! Constraints are applied after the layout, from roots to leaves.
+//! Each constrained node carries its subtree with it.
+//! Then trees without constrained nodes above them are shifted across levels,
+//! as a whole, until they no longer overlap constrained subtrees or each other.
+
+use std::collections::{BTreeMap, HashMap};
+
+use egui::{Pos2, Rect, Vec2, vec2};
+
+use crate::NodeId;
+
+use super::{LayoutConfig, LayoutConstraint, NodeDimensionProvider, SpanningForest, sort_key};
+
+/// Moves nodes of `positions` to satisfy `constraints`, and others out of their way.
+///
+/// `current` returns position of a node before the layout.
+pub(super) fn apply_constraints<P: NodeDimensionProvider>(
+  positions: &mut HashMap<NodeId, Pos2>,
+  forest: &SpanningForest<'_, P>,
+  constraints: &HashMap<NodeId, LayoutConstraint>,
+  config: &LayoutConfig,
+  current: impl Fn(NodeId) -> Pos2,
+) {
+  if constraints.is_empty() {
+    return;
+  }
+
+  // Nodes of each tree from root to leaves, with their parents.
+  let mut trees = Vec::new();
+  for &root in &forest.roots {
+    let mut nodes = vec![(root, None)];
+    let mut next = 0;
+    while next < nodes.len() {
+      let (node, _) = nodes[next];
+      if let Some(children) = forest.children.get(&node) {
+        nodes.extend(children.iter().map(|&child| (child, Some(node))));
+      }
+      next += 1;
+    }
+    trees.push(nodes);
+  }
+
+  let mut fixed = Vec::new();
+  let mut free_trees = Vec::new();
+
+  for tree in &trees {
+    let mut constrained = Vec::new();
+    for &(node, parent) in tree {
+      let Some(constraint) = constraints.get(&node) else {
+        continue;
+      };
+      let Some(&pos) = positions.get(&node) else {
+        continue;
+      };
+
+      let target = match *constraint {
+        LayoutConstraint::Pinned(target) => target,
+        LayoutConstraint::FixedX(x) => Pos2::new(x, pos.y),
+        LayoutConstraint::KeepParentOffset => match parent {
+          Some(parent) => {
+            let parent_pos = positions.get(&parent).copied().unwrap_or_else(|| current(parent));
+            parent_pos + (current(node) - current(parent))
+          }
+          None => pos,
+        },
+      };
+
+      let subtree = subtree(node, forest);
+      let delta = target - pos;
+      for id in &subtree[1..] {
+        if let Some(pos) = positions.get_mut(id) {
+          *pos += delta;
+        }
+      }
+      positions.insert(node, target);
+      constrained.extend(subtree);
+    }
+
+    if constrained.is_empty() {
+      free_trees.push(tree.iter().map(|&(node, _)| node).collect::<Vec<_>>());
+    } else {
+      // Nodes above constrained ones stay together, as the tree they belong to.
+      constrained.sort_unstable();
+      constrained.dedup();
+      let free = tree
+        .iter()
+        .map(|&(node, _)| node)
+        .filter(|node| constrained.binary_search(node).is_err())
+        .collect::<Vec<_>>();
+      if !free.is_empty() {
+        free_trees.push(free);
+      }
+      fixed.extend(constrained);
+    }
+  }
+
+  let horizontal = config.orientation.is_horizontal();
+
+  // Rect of a node in top-to-bottom space, x across levels and y along them.
+  let rect = |id: NodeId, pos: Pos2| {
+    let (width, height) = forest.get_size(id);
+    let pos = if horizontal { Pos2::new(pos.y, pos.x) } else { pos };
+    Rect::from_min_size(pos, vec2(width, height))
+  };
+  let padding = vec2(config.horizontal_spacing, config.vertical_spacing) / 2.0;
+
+  let mut obstacles = Obstacles::default();
+  for &id in &fixed {
+    if let Some(&pos) = positions.get(&id) {
+      obstacles.insert(rect(id, pos));
+    }
+  }
+
+  for tree in free_trees {
+    let rects =
+      tree.iter().filter_map(|&id| Some(rect(id, *positions.get(&id)?))).collect::<Vec<_>>();
+
+    let shift = smallest_shift(&rects, &obstacles, padding);
+    let delta = if horizontal { vec2(0.0, shift) } else { vec2(shift, 0.0) };
+    for id in &tree {
+      if let Some(pos) = positions.get_mut(id) {
+        *pos += delta;
+      }
+    }
+
+    for rect in rects {
+      obstacles.insert(rect.translate(vec2(shift, 0.0)));
+    }
+  }
+}
+
+/// Placed rects indexed by their left side, so the ones around a tree are found
+/// without scanning all of them.
+#[derive(Default)]
+struct Obstacles {
+  /// Rects by left side, with insertion order to tell apart rects with the same side.
+  rects: BTreeMap<(u32, usize), Rect>,
+  max_width: f32,
+}
+
+impl Obstacles {
+  fn insert(&mut self, rect: Rect) {
+    self.max_width = self.max_width.max(rect.width());
+    self.rects.insert((sort_key(rect.min.x), self.rects.len()), rect);
+  }
+
+  /// Returns rects that reach between `min_x` and `max_x`.
+  fn within(&self, min_x: f32, max_x: f32) -> impl Iterator<Item = &Rect> {
+    let from = (sort_key(min_x - self.max_width), 0);
+    let to = (sort_key(max_x), usize::MAX);
+    self.rects.range(from..=to).map(|(_, rect)| rect).filter(move |rect| rect.max.x >= min_x)
+  }
+}
+
+/// Collects the node and all its descendants in the forest.
+fn subtree<P>(node: NodeId, forest: &SpanningForest<'_, P>) -> Vec<NodeId> {
+  let mut nodes = vec![node];
+  let mut next = 0;
+  while next < nodes.len() {
+    if let Some(children) = forest.children.get(&nodes[next]) {
+      nodes.extend(children);
+    }
+    next += 1;
+  }
+  nodes
+}
+
+/// Returns the shift along x closest to zero that moves `rects` off all `obstacles`.
+///
+/// Rects that don't overlap obstacles stay in place.
+/// Moved rects are kept apart from obstacles by twice the `padding`.
+fn smallest_shift(rects: &[Rect], obstacles: &Obstacles, padding: Vec2) -> f32 {
+  let Some(bounds) = rects.iter().copied().reduce(Rect::union) else {
+    return 0.0;
+  };
+
+  let overlaps = obstacles
+    .within(bounds.min.x, bounds.max.x)
+    .filter(|obstacle| obstacle.intersects(bounds))
+    .any(|obstacle| {
+      rects.iter().any(|rect| {
+        let overlap = rect.intersect(*obstacle);
+        overlap.width() > 0.0 && overlap.height() > 0.0
+      })
+    });
+  if !overlaps {
+    return 0.0;
+  }
+
+  // Padded rects may touch but never overlap.
+  let bounds = bounds.expand2(padding);
+  let rects = rects.iter().map(|rect| rect.expand2(padding)).collect::<Vec<_>>();
+
+  // Shifts within `reach` are searched first, and `reach` grows while the range of
+  // forbidden shifts around zero runs to its ends.
+  // Obstacles further away forbid only shifts beyond `reach`.
+  let mut reach = bounds.width().max(1.0);
+  loop {
+    let (min_x, max_x) = (bounds.min.x - reach - padding.x, bounds.max.x + reach + padding.x);
+    let obstacles = obstacles.within(min_x, max_x).map(|obstacle| obstacle.expand2(padding));
+
+    // Each obstacle forbids an open range of shifts for each rect that overlaps it along y.
+    let mut forbidden = Vec::new();
+    for obstacle in obstacles {
+      if obstacle.max.y <= bounds.min.y || obstacle.min.y >= bounds.max.y {
+        continue;
+      }
+      for rect in &rects {
+        if obstacle.max.y > rect.min.y && obstacle.min.y < rect.max.y {
+          forbidden.push((obstacle.min.x - rect.max.x, obstacle.max.x - rect.min.x));
+        }
+      }
+    }
+    forbidden.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // Merge ranges that overlap, the one containing zero gives both nearest free shifts.
+    let mut merged: Vec<(f32, f32)> = Vec::new();
+    for (min, max) in forbidden {
+      match merged.last_mut() {
+        Some(last) if min < last.1 => last.1 = last.1.max(max),
+        _ => merged.push((min, max)),
+      }
+    }
+
+    match merged.iter().find(|(min, max)| *min < 0.0 && 0.0 < *max) {
+      Some(&(min, max)) if -reach < min && max < reach => {
+        return if -min < max { min } else { max };
+      }
+      Some(_) => reach *= 2.0,
+      None => return 0.0,
+    }
+  }
+}
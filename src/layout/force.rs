@@ -0,0 +1,376 @@
+//! Force-directed layout for loosely hierarchical graphs.
+//!
+//! Follows Fruchterman and Reingold: every pair of nodes repels,
+//! wires pull their nodes together, and each iteration moves nodes
+//! at most by a temperature that cools down until the graph settles.
+//! Optional gravity pulls each node towards the level of its depth in the tree.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use egui::{Context, Id, Pos2, Vec2, vec2};
+
+use crate::ui::TreeizeViewer;
+use crate::{NodeId, Treeize};
+
+use super::{
+  LayoutConfig, LayoutGraph, LayoutOrientation, LayoutResult, ViewerInputs, apply_layout,
+  carry_folded, folded_nodes,
+};
+
+/// Temperature below which nodes are considered settled.
+const SETTLED_TEMPERATURE: f32 = 0.5;
+
+/// Limit of iterations for [`LayoutAlgorithm::Force`](super::LayoutAlgorithm::Force)
+/// run at once, in case of cooling that is too slow.
+const MAX_ITERATIONS: u32 = 10_000;
+
+/// Configuration of [`LayoutAlgorithm::Force`](super::LayoutAlgorithm::Force).
+///
+/// Connected nodes settle about [`LayoutConfig::vertical_spacing`] apart, edge to edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ForceConfig {
+  /// Strength of the pull of each node towards the level of its depth,
+  /// with levels placed along [`LayoutConfig::orientation`] as in tree layouts.
+  /// Zero disables it, so hierarchy is not kept.
+  pub depth_gravity: f32,
+
+  /// Factor of the temperature after each iteration, between zero and one.
+  /// Values closer to one settle slower but untangle the graph better.
+  pub cooling: f32,
+
+  /// Number of iterations [`ForceSimulation`] runs on each step,
+  /// which is each frame in the widget.
+  pub iterations_per_frame: u32,
+}
+
+impl Default for ForceConfig {
+  fn default() -> Self {
+    ForceConfig { depth_gravity: 0.0, cooling: 0.95, iterations_per_frame: 5 }
+  }
+}
+
+/// Force-directed layout stepped a few iterations at a time, so the graph settles visibly.
+///
+/// [`TreeizeWidget`](crate::ui::TreeizeWidget) steps it every frame after a
+/// [`TreeizeLayoutSignal`](crate::ui::TreeizeLayoutSignal) with
+/// [`LayoutAlgorithm::Force`](super::LayoutAlgorithm::Force).
+/// Nodes that are held, e.g. dragged by the user, stay where they are and others move around them.
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashSet;
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{ForceSimulation, LayoutAlgorithm, LayoutConfig};
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+///
+/// let config = LayoutConfig { algorithm: LayoutAlgorithm::Force, ..Default::default() };
+/// let mut simulation = ForceSimulation::new(config);
+/// let held = HashSet::from([root]);
+/// while !simulation.is_settled() {
+///   simulation.step(&mut treeize, |_| true, |_| true, None, &held);
+/// }
+///
+/// let pos = |node| treeize.get_node_info(node).unwrap().pos;
+/// assert_eq!(pos(root), egui::pos2(0.0, 0.0));
+/// assert!(pos(child).distance(pos(root)) > config.vertical_spacing);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ForceSimulation {
+  config: LayoutConfig,
+  temperature: f32,
+}
+
+impl ForceSimulation {
+  /// Starts a simulation from the current positions of nodes.
+  #[must_use]
+  pub fn new(config: LayoutConfig) -> Self {
+    ForceSimulation { config, temperature: initial_temperature(&config) }
+  }
+
+  /// Returns configuration of the simulation.
+  #[must_use]
+  pub fn config(&self) -> &LayoutConfig {
+    &self.config
+  }
+
+  /// Returns true if nodes no longer move noticeably.
+  #[must_use]
+  pub fn is_settled(&self) -> bool {
+    self.temperature < SETTLED_TEMPERATURE
+  }
+
+  /// Runs [`ForceConfig::iterations_per_frame`] iterations and applies new positions.
+  ///
+  /// Nodes in `held` are not moved. Other arguments are the same as for
+  /// [`layout_tree`](super::layout_tree).
+  ///
+  /// # Returns
+  ///
+  /// [`LayoutResult`] with positions of all nodes after the iterations.
+  #[allow(clippy::implicit_hasher)]
+  pub fn step<T>(
+    &mut self,
+    treeize: &mut Treeize<T>,
+    has_output: impl FnMut(NodeId) -> bool,
+    has_input: impl FnMut(NodeId) -> bool,
+    node_sizes: Option<&HashMap<NodeId, Vec2>>,
+    held: &HashSet<NodeId>,
+  ) -> LayoutResult {
+    let folded = folded_nodes(treeize, &self.config);
+    let graph = LayoutGraph::new(
+      treeize,
+      node_sizes,
+      has_output,
+      has_input,
+      |_, _| 1.0,
+      &folded,
+      &self.config,
+    );
+
+    let mut bodies = Simulation::new(&graph, &self.config, |node| held.contains(&node));
+    bodies.run(&mut self.temperature, self.config.force.iterations_per_frame);
+
+    let mut positions = bodies.positions();
+    carry_folded(&mut positions, &folded, treeize);
+    let result = LayoutResult::new(&graph, treeize, positions, &self.config);
+
+    apply_layout(treeize, &result.positions);
+    result
+  }
+
+  /// Runs iterations like [`ForceSimulation::step`] with pins and sizes of nodes taken
+  /// as in [`layout_with_viewer`](super::layout_with_viewer).
+  #[allow(clippy::implicit_hasher)]
+  pub fn step_with_viewer<T, V>(
+    &mut self,
+    treeize: &mut Treeize<T>,
+    viewer: &mut V,
+    held: &HashSet<NodeId>,
+    ctx: &Context,
+    treeize_id: Id,
+  ) -> LayoutResult
+  where
+    V: TreeizeViewer<T>,
+  {
+    let inputs = ViewerInputs::new(treeize, viewer, ctx, treeize_id);
+    self.step(
+      treeize,
+      |node_id| inputs.has_output(node_id),
+      |node_id| inputs.has_input(node_id),
+      inputs.node_sizes(),
+      held,
+    )
+  }
+}
+
+/// Arranges nodes of the graph by running the simulation until it settles.
+pub(super) fn force_layout(
+  graph: &LayoutGraph<'_>,
+  config: &LayoutConfig,
+) -> HashMap<NodeId, Pos2> {
+  let mut bodies = Simulation::new(graph, config, |_| false);
+  let mut temperature = initial_temperature(config);
+  bodies.run(&mut temperature, MAX_ITERATIONS);
+  bodies.positions()
+}
+
+/// Moves are limited by this temperature at the start.
+fn initial_temperature(config: &LayoutConfig) -> f32 {
+  config.vertical_spacing.max(1.0) * 2.0
+}
+
+/// Node of the simulation.
+struct Body {
+  id: NodeId,
+  /// Top-left corner before the simulation.
+  start: Pos2,
+  center: Pos2,
+  size: Vec2,
+  depth: usize,
+  held: bool,
+}
+
+impl Body {
+  /// Radius of the circle around the node.
+  fn reach(&self) -> f32 {
+    self.size.length() / 2.0
+  }
+}
+
+/// Nodes and wires of the graph being simulated, ordered as nodes of the graph.
+struct Simulation {
+  bodies: Vec<Body>,
+  edges: Vec<(usize, usize)>,
+  ideal_gap: f32,
+  depth_gravity: f32,
+  cooling: f32,
+  /// Direction from roots to leaves.
+  axis: Vec2,
+  /// Position of the roots' level along the axis.
+  origin: f32,
+  /// Distance between levels along the axis.
+  level: f32,
+}
+
+impl Simulation {
+  fn new(graph: &LayoutGraph<'_>, config: &LayoutConfig, held: impl Fn(NodeId) -> bool) -> Self {
+    let index =
+      graph.nodes().iter().enumerate().map(|(idx, &id)| (id, idx)).collect::<HashMap<_, _>>();
+
+    let mut depths = HashMap::new();
+    let mut queue = graph.roots().iter().map(|&root| (root, 0)).collect::<VecDeque<_>>();
+    while let Some((node, depth)) = queue.pop_front() {
+      depths.insert(node, depth);
+      queue.extend(graph.tree_children(node).iter().map(|&child| (child, depth + 1)));
+    }
+
+    // Nodes that share a position are spread on a spiral around it,
+    // so forces between them have a direction.
+    let mut shared = HashMap::new();
+    let bodies = graph
+      .nodes()
+      .iter()
+      .map(|&id| {
+        let size = graph.size(id);
+        let pos = graph.position(id);
+        let held = held(id);
+        let count = shared.entry((pos.x.to_bits(), pos.y.to_bits())).or_insert(0u32);
+        #[allow(clippy::cast_precision_loss)]
+        let spread = if *count == 0 || held {
+          Vec2::ZERO
+        } else {
+          Vec2::angled(*count as f32 * 2.399_963) * config.vertical_spacing * (*count as f32).sqrt()
+        };
+        *count += 1;
+
+        Body {
+          id,
+          start: pos,
+          center: pos + spread + size / 2.0,
+          size,
+          depth: depths.get(&id).copied().unwrap_or(0),
+          held,
+        }
+      })
+      .collect::<Vec<_>>();
+
+    let mut edges = graph
+      .nodes()
+      .iter()
+      .flat_map(|&parent| graph.children(parent).iter().map(move |&child| (parent, child)))
+      .filter(|(parent, child)| parent != child)
+      .map(|(parent, child)| {
+        let (a, b) = (index[&parent], index[&child]);
+        (a.min(b), a.max(b))
+      })
+      .collect::<Vec<_>>();
+    edges.sort_unstable();
+    edges.dedup();
+
+    let axis = match config.orientation {
+      LayoutOrientation::TopToBottom => vec2(0.0, 1.0),
+      LayoutOrientation::BottomToTop => vec2(0.0, -1.0),
+      LayoutOrientation::LeftToRight => vec2(1.0, 0.0),
+      LayoutOrientation::RightToLeft => vec2(-1.0, 0.0),
+    };
+
+    #[allow(clippy::cast_precision_loss)]
+    let mean_extent = if bodies.is_empty() {
+      0.0
+    } else {
+      bodies.iter().map(|body| body.size.dot(axis).abs()).sum::<f32>() / bodies.len() as f32
+    };
+
+    Simulation {
+      bodies,
+      edges,
+      ideal_gap: config.vertical_spacing.max(1.0),
+      depth_gravity: config.force.depth_gravity.max(0.0),
+      cooling: config.force.cooling.clamp(0.0, 0.999),
+      axis,
+      origin: config.start_pos.to_vec2().dot(axis) + mean_extent / 2.0,
+      level: config.vertical_spacing + mean_extent,
+    }
+  }
+
+  /// Runs up to `iterations` iterations, cooling down the `temperature`.
+  fn run(&mut self, temperature: &mut f32, iterations: u32) {
+    for _ in 0..iterations {
+      if *temperature < SETTLED_TEMPERATURE {
+        break;
+      }
+      self.iterate(*temperature);
+      *temperature *= self.cooling;
+    }
+  }
+
+  fn iterate(&mut self, temperature: f32) {
+    let k = self.ideal_gap;
+    let mut shifts = vec![Vec2::ZERO; self.bodies.len()];
+
+    // Every pair repels, stronger as the gap between the nodes closes.
+    for i in 0..self.bodies.len() {
+      for j in i + 1..self.bodies.len() {
+        let (a, b) = (&self.bodies[i], &self.bodies[j]);
+        let delta = a.center - b.center;
+        let distance = delta.length();
+        #[allow(clippy::cast_precision_loss)]
+        let direction =
+          if distance > 0.0 { delta / distance } else { Vec2::angled((i + j) as f32) };
+        let gap = (distance - a.reach() - b.reach()).max(1.0);
+
+        let force = direction * (k * k / gap);
+        shifts[i] += force;
+        shifts[j] -= force;
+      }
+    }
+
+    // Wires pull their nodes together.
+    for &(i, j) in &self.edges {
+      let (a, b) = (&self.bodies[i], &self.bodies[j]);
+      let delta = b.center - a.center;
+      let distance = delta.length();
+      if distance <= 0.0 {
+        continue;
+      }
+      let gap = (distance - a.reach() - b.reach()).max(0.0);
+
+      let force = delta / distance * (gap * gap / k);
+      shifts[i] += force;
+      shifts[j] -= force;
+    }
+
+    if self.depth_gravity > 0.0 {
+      for (body, shift) in self.bodies.iter().zip(&mut shifts) {
+        #[allow(clippy::cast_precision_loss)]
+        let target = self.origin + body.depth as f32 * self.level;
+        *shift +=
+          self.axis * (self.depth_gravity * (target - body.center.to_vec2().dot(self.axis)));
+      }
+    }
+
+    for (body, shift) in self.bodies.iter_mut().zip(shifts) {
+      let length = shift.length();
+      if body.held || length <= 0.0 {
+        continue;
+      }
+      body.center += shift / length * length.min(temperature);
+    }
+  }
+
+  /// Returns top-left corners of nodes.
+  fn positions(&self) -> HashMap<NodeId, Pos2> {
+    self
+      .bodies
+      .iter()
+      .map(|body| {
+        let pos = if body.held { body.start } else { body.center - body.size / 2.0 };
+        (body.id, pos)
+      })
+      .collect()
+  }
+}
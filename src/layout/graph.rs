@@ -0,0 +1,302 @@
+//! Read-only view of the graph for layout algorithms.
+
+use std::collections::{HashMap, HashSet};
+
+use egui::{Pos2, Vec2, vec2};
+
+use crate::{InPinId, NodeId, OutPinId, Treeize};
+
+use super::{LayoutConfig, LayoutOrientation, NodeDimensionProvider, SpanningForest};
+
+/// Layout algorithm that can be plugged into [`layout_tree_with`](super::layout_tree_with)
+/// and [`TreeizeLayoutSignal`](crate::ui::TreeizeLayoutSignal).
+///
+/// [`LayoutConfig`] implements it with the built-in algorithms.
+///
+/// # Examples
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use egui::Pos2;
+/// # use egui_treeize::{InPinId, NodeId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, LayoutGraph, TreeLayout, layout_tree_with};
+/// /// Lists nodes in a column, each indented under its parent, like a file tree.
+/// struct Indented;
+///
+/// impl TreeLayout for Indented {
+///   fn layout(&self, graph: &LayoutGraph<'_>) -> HashMap<NodeId, Pos2> {
+///     let mut positions = HashMap::new();
+///     let mut y = 0.0;
+///     let mut stack = graph.roots().iter().rev().map(|&root| (root, 0.0)).collect::<Vec<_>>();
+///     while let Some((node, x)) = stack.pop() {
+///       positions.insert(node, egui::pos2(x, y));
+///       y += graph.size(node).y + 10.0;
+///       stack.extend(graph.tree_children(node).iter().rev().map(|&child| (child, x + 20.0)));
+///     }
+///     positions
+///   }
+/// }
+///
+/// let mut treeize = Treeize::new();
+/// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+///
+/// let sizes = HashMap::from([(root, egui::vec2(100.0, 30.0)), (child, egui::vec2(100.0, 30.0))]);
+/// let config = LayoutConfig::default();
+/// let result =
+///   layout_tree_with(&treeize, &Indented, config, |_| true, |_| true, Some(&sizes), &HashMap::new());
+///
+/// assert_eq!(result.positions[&child], egui::pos2(20.0, 40.0));
+/// ```
+pub trait TreeLayout {
+  /// Returns positions (top-left corners) of nodes of the graph.
+  /// Nodes left out keep their current positions.
+  ///
+  /// Layout should depend only on the graph, not on iteration order of hash maps,
+  /// to keep results the same between runs.
+  fn layout(&self, graph: &LayoutGraph<'_>) -> HashMap<NodeId, Pos2>;
+}
+
+/// Read-only view of the graph given to a [`TreeLayout`].
+///
+/// Contains nodes and wires that take part in the layout,
+/// without nodes that are folded or wires between pins that are not shown.
+/// Nodes are listed in the order of their [`NodeId`]s and children in the order
+/// of [`Treeize::sorted_wires`], so the graph is the same on every run.
+/// Cycles are broken by a spanning forest found by depth-first search,
+/// see [`layout_tree`](super::layout_tree).
+pub struct LayoutGraph<'a> {
+  nodes: Vec<NodeId>,
+  positions: HashMap<NodeId, Pos2>,
+  node_sizes: Option<&'a HashMap<NodeId, Vec2>>,
+  default_size: Vec2,
+  children: HashMap<NodeId, Vec<NodeId>>,
+  roots: Vec<NodeId>,
+  tree_children: HashMap<NodeId, Vec<NodeId>>,
+  tree_parents: HashMap<NodeId, NodeId>,
+  back_edges: HashSet<(NodeId, NodeId)>,
+  weights: HashMap<(NodeId, NodeId), f32>,
+}
+
+impl<'a> LayoutGraph<'a> {
+  /// Collects nodes and wires of the treeize, leaving out `folded` nodes.
+  ///
+  /// Wires between the same nodes take the weight of the first one.
+  pub(super) fn new<T, H>(
+    treeize: &Treeize<T>,
+    node_sizes: Option<&'a HashMap<NodeId, Vec2>>,
+    mut has_output: impl FnMut(NodeId) -> bool,
+    mut has_input: impl FnMut(NodeId) -> bool,
+    mut weight: impl FnMut(OutPinId, InPinId) -> f32,
+    folded: &HashMap<NodeId, NodeId, H>,
+    config: &LayoutConfig,
+  ) -> Self
+  where
+    H: std::hash::BuildHasher,
+  {
+    let nodes = treeize
+      .node_ids()
+      .map(|(node_id, _)| node_id)
+      .filter(|node_id| !folded.contains_key(node_id))
+      .collect::<Vec<_>>();
+
+    // Build children map from wires, in an order that doesn't change between runs
+    let mut children: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let mut weights = HashMap::new();
+    for (out_pin, in_pin) in treeize.sorted_wires() {
+      let from_node = out_pin.node;
+      let to_node = in_pin.node;
+
+      if folded.contains_key(&from_node) || folded.contains_key(&to_node) {
+        continue;
+      }
+      if has_output(from_node) && has_input(to_node) {
+        children.entry(from_node).or_default().push(to_node);
+        weights.entry((from_node, to_node)).or_insert_with(|| weight(out_pin, in_pin));
+      }
+    }
+
+    // Nodes that were not measured take the spacing, along levels and across them.
+    let default_size = if config.orientation.is_horizontal() {
+      vec2(config.vertical_spacing, config.horizontal_spacing)
+    } else {
+      vec2(config.horizontal_spacing, config.vertical_spacing)
+    };
+
+    let positions = nodes.iter().map(|&node_id| (node_id, treeize.nodes[node_id.0].pos)).collect();
+
+    let mut graph = LayoutGraph {
+      nodes,
+      positions,
+      node_sizes,
+      default_size,
+      children,
+      roots: Vec::new(),
+      tree_children: HashMap::new(),
+      tree_parents: HashMap::new(),
+      back_edges: HashSet::new(),
+      weights,
+    };
+
+    let forest = SpanningForest::new(&graph.nodes, &graph);
+    let SpanningForest { roots, children: tree_children, parents, back_edges, .. } = forest;
+    graph.roots = roots;
+    graph.tree_children = tree_children;
+    graph.tree_parents = parents;
+    graph.back_edges = back_edges;
+    graph
+  }
+
+  /// Returns all nodes of the graph.
+  #[must_use]
+  pub fn nodes(&self) -> &[NodeId] {
+    &self.nodes
+  }
+
+  /// Returns roots of the spanning forest.
+  ///
+  /// Nodes without parents come first,
+  /// followed by nodes that are reachable only through cycles.
+  #[must_use]
+  pub fn roots(&self) -> &[NodeId] {
+    &self.roots
+  }
+
+  /// Returns all children of the node, in the order of [`Treeize::sorted_wires`].
+  #[must_use]
+  pub fn children(&self, node: NodeId) -> &[NodeId] {
+    self.children.get(&node).map_or(&[], Vec::as_slice)
+  }
+
+  /// Returns children of the node in the spanning forest.
+  ///
+  /// Each node except roots is a tree child of exactly one parent,
+  /// so following tree children from roots visits every node once.
+  #[must_use]
+  pub fn tree_children(&self, node: NodeId) -> &[NodeId] {
+    self.tree_children.get(&node).map_or(&[], Vec::as_slice)
+  }
+
+  /// Returns size of the node as measured by the widget.
+  /// Nodes that were not measured yet get a size from the layout spacing.
+  #[must_use]
+  pub fn size(&self, node: NodeId) -> Vec2 {
+    self.node_sizes.and_then(|sizes| sizes.get(&node)).copied().unwrap_or(self.default_size)
+  }
+
+  /// Returns position (top-left corner) of the node before the layout.
+  #[must_use]
+  pub fn position(&self, node: NodeId) -> Pos2 {
+    self.positions.get(&node).copied().unwrap_or(Pos2::ZERO)
+  }
+
+  /// Returns weight of the wire from `parent` to `child`, one if it has none.
+  ///
+  /// [`LayoutAlgorithm::Tidy`](super::LayoutAlgorithm::Tidy) places children
+  /// this many times [`LayoutConfig::vertical_spacing`] away from their parents,
+  /// see [`layout_tree_weighted`](super::layout_tree_weighted).
+  #[must_use]
+  pub fn weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    self.weights.get(&(parent, child)).copied().unwrap_or(1.0)
+  }
+
+  /// Returns true if the wire from `parent` to `child` closes a cycle
+  /// and is left out of the spanning forest.
+  #[must_use]
+  pub fn is_back_edge(&self, parent: NodeId, child: NodeId) -> bool {
+    self.back_edges.contains(&(parent, child))
+  }
+
+  /// Returns node pairs of wires that close cycles.
+  pub(super) fn back_edges(&self) -> &HashSet<(NodeId, NodeId)> {
+    &self.back_edges
+  }
+
+  /// Returns wires of the treeize that are back edges.
+  pub(super) fn back_wires<T>(&self, treeize: &Treeize<T>) -> HashSet<(OutPinId, InPinId)> {
+    treeize
+      .wires()
+      .filter(|(out_pin, in_pin)| self.back_edges.contains(&(out_pin.node, in_pin.node)))
+      .collect()
+  }
+
+  /// Returns the graph with sizes as seen by layouts computed top-to-bottom.
+  pub(super) fn oriented(&self, orientation: LayoutOrientation) -> Oriented<'_, 'a> {
+    Oriented { graph: self, horizontal: orientation.is_horizontal() }
+  }
+
+  /// Returns the spanning forest with sizes as seen by layouts computed top-to-bottom.
+  pub(super) fn oriented_forest(&self, orientation: LayoutOrientation) -> OrientedForest<'_, 'a> {
+    OrientedForest(self.oriented(orientation))
+  }
+}
+
+impl NodeDimensionProvider for LayoutGraph<'_> {
+  fn get_size(&self, node_id: NodeId) -> (f32, f32) {
+    let size = self.size(node_id);
+    (size.x, size.y)
+  }
+
+  fn get_children(&self, node_id: NodeId) -> Vec<NodeId> {
+    self.children(node_id).to_vec()
+  }
+
+  fn get_weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    self.weight(parent, child)
+  }
+}
+
+/// Graph for layouts that are computed top-to-bottom and then oriented.
+pub(super) struct Oriented<'g, 'a> {
+  graph: &'g LayoutGraph<'a>,
+  horizontal: bool,
+}
+
+impl NodeDimensionProvider for Oriented<'_, '_> {
+  fn get_size(&self, node_id: NodeId) -> (f32, f32) {
+    // Horizontal orientations swap the axes.
+    let size = self.graph.size(node_id);
+    if self.horizontal { (size.y, size.x) } else { (size.x, size.y) }
+  }
+
+  fn get_children(&self, node_id: NodeId) -> Vec<NodeId> {
+    self.graph.children(node_id).to_vec()
+  }
+
+  fn get_weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    self.graph.weight(parent, child)
+  }
+}
+
+/// Spanning forest of the graph for layouts that are computed top-to-bottom and then oriented.
+///
+/// Children are tree children, so the forest found by [`LayoutGraph::new`] is used
+/// without searching the graph again.
+pub(super) struct OrientedForest<'g, 'a>(Oriented<'g, 'a>);
+
+impl OrientedForest<'_, '_> {
+  /// Returns parent of the node in the spanning forest, `None` for roots.
+  pub(super) fn parent(&self, node: NodeId) -> Option<NodeId> {
+    self.0.graph.tree_parents.get(&node).copied()
+  }
+
+  /// Returns children of the node in the spanning forest.
+  pub(super) fn children(&self, node: NodeId) -> &[NodeId] {
+    self.0.graph.tree_children(node)
+  }
+}
+
+impl NodeDimensionProvider for OrientedForest<'_, '_> {
+  fn get_size(&self, node_id: NodeId) -> (f32, f32) {
+    self.0.get_size(node_id)
+  }
+
+  fn get_children(&self, node_id: NodeId) -> Vec<NodeId> {
+    self.children(node_id).to_vec()
+  }
+
+  fn get_weight(&self, parent: NodeId, child: NodeId) -> f32 {
+    self.0.get_weight(parent, child)
+  }
+}
@@ -0,0 +1,559 @@
+//! Layered (Sugiyama) layout for directed acyclic graphs.
+//!
+//! Steps:
+//! 1. Cycles are broken by reversing back edges of the spanning forest.
+//! 2. Nodes are assigned to layers by the longest path from sources.
+//! 3. Edges spanning several layers are split with dummy nodes.
+//! 4. Crossings are reduced by barycenter sweeps.
+//! 5. X coordinates are assigned with the Brandes-Köpf algorithm.
+
+use std::collections::{HashMap, HashSet};
+
+use egui::{Pos2, Vec2, pos2};
+
+use crate::NodeId;
+
+use super::{LayoutConfig, NodeDimensionProvider};
+
+/// Maximum number of down and up barycenter sweep pairs.
+const MAX_SWEEPS: usize = 24;
+
+/// Number of sweeps without improvement after which crossing minimization stops.
+const MAX_SWEEPS_WITHOUT_IMPROVEMENT: usize = 4;
+
+/// Vertex of the layered graph.
+/// Either a node of the treeize or a dummy node on a long edge.
+struct Vertex {
+  node: Option<NodeId>,
+  width: f32,
+  height: f32,
+  layer: usize,
+  preds: Vec<usize>,
+  succs: Vec<usize>,
+}
+
+impl Vertex {
+  const fn is_dummy(&self) -> bool {
+    self.node.is_none()
+  }
+}
+
+struct LayeredGraph {
+  vertices: Vec<Vertex>,
+  layers: Vec<Vec<usize>>,
+  /// Index of each vertex in its layer.
+  order: Vec<usize>,
+}
+
+/// Arranges nodes in layers so that all edges point to the next layers.
+///
+/// Every node is placed exactly once, no matter how many parents it has.
+/// Edges in `back_edges` are reversed, they must break all cycles of the graph.
+pub(super) fn layered_layout<P: NodeDimensionProvider>(
+  nodes: &[NodeId],
+  provider: &P,
+  back_edges: &HashSet<(NodeId, NodeId)>,
+  config: &LayoutConfig,
+) -> HashMap<NodeId, Pos2> {
+  let mut positions = HashMap::new();
+  if nodes.is_empty() {
+    return positions;
+  }
+
+  let mut graph = LayeredGraph::new(nodes, provider, back_edges);
+  graph.minimize_crossings();
+
+  let xs = graph.assign_x(config.horizontal_spacing);
+
+  let mut layer_tops = Vec::with_capacity(graph.layers.len());
+  let mut top = 0.0;
+  for layer in &graph.layers {
+    layer_tops.push(top);
+    let height = layer.iter().map(|&v| graph.vertices[v].height).fold(0.0, f32::max);
+    top += height + config.vertical_spacing;
+  }
+
+  let min_x = graph
+    .vertices
+    .iter()
+    .zip(&xs)
+    .filter(|(vertex, _)| !vertex.is_dummy())
+    .map(|(vertex, x)| x - vertex.width / 2.0)
+    .fold(f32::INFINITY, f32::min);
+
+  for (vertex, x) in graph.vertices.iter().zip(&xs) {
+    let Some(node) = vertex.node else {
+      continue;
+    };
+
+    let pos = pos2(
+      config.start_pos.x + x - vertex.width / 2.0 - min_x,
+      config.start_pos.y + layer_tops[vertex.layer],
+    );
+    let size = Vec2::new(vertex.width, vertex.height);
+    positions.insert(node, config.orientation.orient(config.start_pos, pos, size));
+  }
+
+  positions
+}
+
+impl LayeredGraph {
+  fn new<P: NodeDimensionProvider>(
+    nodes: &[NodeId],
+    provider: &P,
+    back_edges: &HashSet<(NodeId, NodeId)>,
+  ) -> Self {
+    let index: HashMap<NodeId, usize> =
+      nodes.iter().enumerate().map(|(idx, node)| (*node, idx)).collect();
+
+    // Unique edges between distinct nodes, back edges are reversed.
+    let mut seen = HashSet::new();
+    let mut succs = vec![Vec::new(); nodes.len()];
+    for (from, node) in nodes.iter().enumerate() {
+      for child in provider.get_children(*node) {
+        let Some(&to) = index.get(&child) else {
+          continue;
+        };
+        let (from, to) = if back_edges.contains(&(*node, child)) { (to, from) } else { (from, to) };
+        if from != to && seen.insert((from, to)) {
+          succs[from].push(to);
+        }
+      }
+    }
+
+    let layer = longest_path_layers(&succs);
+
+    let mut vertices = nodes
+      .iter()
+      .zip(&layer)
+      .map(|(node, &layer)| {
+        let (width, height) = provider.get_size(*node);
+        Vertex { node: Some(*node), width, height, layer, preds: Vec::new(), succs: Vec::new() }
+      })
+      .collect::<Vec<_>>();
+
+    // Split long edges with dummy vertices.
+    for (from, tos) in succs.iter().enumerate() {
+      for &to in tos {
+        let mut prev = from;
+        for dummy_layer in layer[from] + 1..layer[to] {
+          let dummy = vertices.len();
+          vertices.push(Vertex {
+            node: None,
+            width: 0.0,
+            height: 0.0,
+            layer: dummy_layer,
+            preds: vec![prev],
+            succs: Vec::new(),
+          });
+          vertices[prev].succs.push(dummy);
+          prev = dummy;
+        }
+        vertices[prev].succs.push(to);
+        vertices[to].preds.push(prev);
+      }
+    }
+
+    let layer_count = vertices.iter().map(|v| v.layer + 1).max().unwrap_or(0);
+    let mut layers = vec![Vec::new(); layer_count];
+
+    // Initial order: depth-first discovery from vertices of the first layers.
+    let mut visited = vec![false; vertices.len()];
+    let mut roots = (0..nodes.len()).collect::<Vec<_>>();
+    roots.sort_by_key(|&v| vertices[v].layer);
+    for root in roots {
+      if visited[root] {
+        continue;
+      }
+      visited[root] = true;
+      let mut stack = vec![root];
+      while let Some(v) = stack.pop() {
+        layers[vertices[v].layer].push(v);
+        for &succ in vertices[v].succs.iter().rev() {
+          if !visited[succ] {
+            visited[succ] = true;
+            stack.push(succ);
+          }
+        }
+      }
+    }
+
+    let mut order = vec![0; vertices.len()];
+    for layer in &layers {
+      for (idx, &v) in layer.iter().enumerate() {
+        order[v] = idx;
+      }
+    }
+
+    LayeredGraph { vertices, layers, order }
+  }
+
+  fn update_order(&mut self) {
+    for layer in &self.layers {
+      for (idx, &v) in layer.iter().enumerate() {
+        self.order[v] = idx;
+      }
+    }
+  }
+
+  /// Reorders vertices within layers with barycenter sweeps, keeping the best order found.
+  fn minimize_crossings(&mut self) {
+    let mut best_layers = self.layers.clone();
+    let mut best_crossings = self.crossings();
+    let mut without_improvement = 0;
+
+    for _ in 0..MAX_SWEEPS {
+      if best_crossings == 0 || without_improvement >= MAX_SWEEPS_WITHOUT_IMPROVEMENT {
+        break;
+      }
+
+      for layer in 1..self.layers.len() {
+        self.sort_by_barycenter(layer, true);
+      }
+      for layer in (0..self.layers.len().saturating_sub(1)).rev() {
+        self.sort_by_barycenter(layer, false);
+      }
+
+      let crossings = self.crossings();
+      if crossings < best_crossings {
+        best_crossings = crossings;
+        best_layers.clone_from(&self.layers);
+        without_improvement = 0;
+      } else {
+        without_improvement += 1;
+      }
+    }
+
+    self.layers = best_layers;
+    self.update_order();
+  }
+
+  /// Sorts the layer by average position of neighbors in the previous or next layer.
+  /// Vertices without neighbors keep their position.
+  #[allow(clippy::cast_precision_loss)]
+  fn sort_by_barycenter(&mut self, layer: usize, use_preds: bool) {
+    let mut keyed = self.layers[layer]
+      .iter()
+      .map(|&v| {
+        let vertex = &self.vertices[v];
+        let neighbors = if use_preds { &vertex.preds } else { &vertex.succs };
+        let key = if neighbors.is_empty() {
+          self.order[v] as f32
+        } else {
+          neighbors.iter().map(|&n| self.order[n] as f32).sum::<f32>() / neighbors.len() as f32
+        };
+        (key, v)
+      })
+      .collect::<Vec<_>>();
+
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    for (idx, (_, v)) in keyed.into_iter().enumerate() {
+      self.layers[layer][idx] = v;
+      self.order[v] = idx;
+    }
+  }
+
+  /// Counts edge crossings between all pairs of adjacent layers.
+  fn crossings(&self) -> usize {
+    let mut total = 0;
+    for layer in self.layers.iter().take(self.layers.len().saturating_sub(1)) {
+      let mut edges = layer
+        .iter()
+        .flat_map(|&u| self.vertices[u].succs.iter().map(move |&v| (u, v)))
+        .map(|(u, v)| (self.order[u], self.order[v]))
+        .collect::<Vec<_>>();
+      edges.sort_unstable();
+
+      // Count inversions of target positions with a Fenwick tree.
+      let size = edges.iter().map(|&(_, to)| to + 1).max().unwrap_or(0);
+      let mut tree = vec![0usize; size + 1];
+      for (seen, &(_, to)) in edges.iter().enumerate() {
+        let mut not_greater = 0;
+        let mut idx = to + 1;
+        while idx > 0 {
+          not_greater += tree[idx];
+          idx &= idx - 1;
+        }
+        total += seen - not_greater;
+
+        let mut idx = to + 1;
+        while idx <= size {
+          tree[idx] += 1;
+          idx += idx & idx.wrapping_neg();
+        }
+      }
+    }
+    total
+  }
+
+  /// Assigns x coordinates of vertex centers with the Brandes-Köpf algorithm.
+  fn assign_x(&self, spacing: f32) -> Vec<f32> {
+    let conflicts = self.type1_conflicts();
+
+    let mut alignments = Vec::with_capacity(4);
+    for downward in [true, false] {
+      for leftmost in [true, false] {
+        let mut layers = self.layers.clone();
+        if !downward {
+          layers.reverse();
+        }
+        if !leftmost {
+          for layer in &mut layers {
+            layer.reverse();
+          }
+        }
+
+        let root = self.vertical_alignment(&layers, &conflicts, downward);
+        let mut xs = self.horizontal_compaction(&layers, &root, spacing);
+        if !leftmost {
+          for x in &mut xs {
+            *x = -*x;
+          }
+        }
+        alignments.push((leftmost, xs));
+      }
+    }
+
+    // Align all layouts to the one with the smallest width.
+    let bounds = |xs: &[f32]| {
+      self.vertices.iter().zip(xs).fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (v, x)| {
+        (min.min(x - v.width / 2.0), max.max(x + v.width / 2.0))
+      })
+    };
+    let smallest = alignments
+      .iter()
+      .map(|(_, xs)| bounds(xs))
+      .enumerate()
+      .min_by(|(_, a), (_, b)| (a.1 - a.0).total_cmp(&(b.1 - b.0)))
+      .map_or(0, |(idx, _)| idx);
+    let (target_min, target_max) = bounds(&alignments[smallest].1);
+
+    for (leftmost, xs) in &mut alignments {
+      let (min, max) = bounds(xs);
+      let delta = if *leftmost { target_min - min } else { target_max - max };
+      for x in xs.iter_mut() {
+        *x += delta;
+      }
+    }
+
+    // Balance: average of the two median candidates.
+    (0..self.vertices.len())
+      .map(|v| {
+        let mut candidates =
+          [alignments[0].1[v], alignments[1].1[v], alignments[2].1[v], alignments[3].1[v]];
+        candidates.sort_by(f32::total_cmp);
+        f32::midpoint(candidates[1], candidates[2])
+      })
+      .collect()
+  }
+
+  /// Marks edges that cross inner segments, i.e. edges between two dummy vertices.
+  ///
+  /// Inner segments are kept straight, so crossing edges are excluded from alignment.
+  fn type1_conflicts(&self) -> HashSet<(usize, usize)> {
+    let mut conflicts = HashSet::new();
+
+    for pair in self.layers.windows(2) {
+      let (prev_layer, layer) = (&pair[0], &pair[1]);
+      let Some(&last) = layer.last() else {
+        continue;
+      };
+
+      let mut k0 = 0;
+      let mut scan_pos = 0;
+      for (i, &v) in layer.iter().enumerate() {
+        let inner = if self.vertices[v].is_dummy() {
+          self.vertices[v].preds.iter().copied().find(|&u| self.vertices[u].is_dummy())
+        } else {
+          None
+        };
+        let k1 = inner.map_or(prev_layer.len(), |u| self.order[u]);
+
+        if inner.is_some() || v == last {
+          for &scan in &layer[scan_pos..=i] {
+            for &u in &self.vertices[scan].preds {
+              let pos = self.order[u];
+              if (pos < k0 || k1 < pos)
+                && !(self.vertices[u].is_dummy() && self.vertices[scan].is_dummy())
+              {
+                conflicts.insert((u, scan));
+              }
+            }
+          }
+          scan_pos = i + 1;
+          k0 = k1;
+        }
+      }
+    }
+
+    conflicts
+  }
+
+  /// Aligns each vertex with a median neighbor in the previous layer of the sweep into blocks.
+  ///
+  /// Returns the root of each vertex's block.
+  fn vertical_alignment(
+    &self,
+    layers: &[Vec<usize>],
+    conflicts: &HashSet<(usize, usize)>,
+    downward: bool,
+  ) -> Vec<usize> {
+    let count = self.vertices.len();
+    let mut root = (0..count).collect::<Vec<_>>();
+    let mut align = (0..count).collect::<Vec<_>>();
+
+    let mut pos = vec![0; count];
+    for layer in layers {
+      for (idx, &v) in layer.iter().enumerate() {
+        pos[v] = idx;
+      }
+    }
+
+    let has_conflict =
+      |a: usize, b: usize| conflicts.contains(&(a, b)) || conflicts.contains(&(b, a));
+
+    for layer in layers {
+      let mut prev_idx: Option<usize> = None;
+
+      for &v in layer {
+        let vertex = &self.vertices[v];
+        let mut neighbors = if downward { vertex.preds.clone() } else { vertex.succs.clone() };
+        if neighbors.is_empty() {
+          continue;
+        }
+        neighbors.sort_by_key(|&w| pos[w]);
+
+        let last = neighbors.len() - 1;
+        for &w in &neighbors[last / 2..=last.div_ceil(2)] {
+          if align[v] == v && prev_idx.is_none_or(|prev| prev < pos[w]) && !has_conflict(v, w) {
+            align[w] = v;
+            root[v] = root[w];
+            align[v] = root[v];
+            prev_idx = Some(pos[w]);
+          }
+        }
+      }
+    }
+
+    root
+  }
+
+  /// Places blocks as far left as separation allows and then pulls them right
+  /// towards their successors to remove unused space.
+  fn horizontal_compaction(&self, layers: &[Vec<usize>], root: &[usize], spacing: f32) -> Vec<f32> {
+    let count = self.vertices.len();
+
+    // Block graph: edge from block of the left neighbor to the block of the vertex.
+    let mut block_succs: HashMap<usize, Vec<(usize, f32)>> = HashMap::new();
+    let mut block_preds: HashMap<usize, Vec<(usize, f32)>> = HashMap::new();
+    let mut blocks = Vec::new();
+    let mut is_block = vec![false; count];
+
+    for layer in layers {
+      let mut left: Option<usize> = None;
+      for &v in layer {
+        let v_root = root[v];
+        if !is_block[v_root] {
+          is_block[v_root] = true;
+          blocks.push(v_root);
+        }
+
+        if let Some(u) = left {
+          let u_root = root[u];
+          let gap = if self.vertices[u].is_dummy() || self.vertices[v].is_dummy() {
+            spacing / 2.0
+          } else {
+            spacing
+          };
+          let sep = f32::midpoint(self.vertices[u].width, self.vertices[v].width) + gap;
+          block_succs.entry(u_root).or_default().push((v_root, sep));
+          block_preds.entry(v_root).or_default().push((u_root, sep));
+        }
+        left = Some(v);
+      }
+    }
+
+    let mut xs = vec![0.0f32; count];
+
+    // First pass: place blocks with minimum distance from their left neighbors.
+    for block in post_order(&blocks, &block_preds) {
+      xs[block] = block_preds
+        .get(&block)
+        .into_iter()
+        .flatten()
+        .map(|&(pred, sep)| xs[pred] + sep)
+        .fold(0.0, f32::max);
+    }
+
+    // Second pass: move blocks right towards their right neighbors.
+    for block in post_order(&blocks, &block_succs) {
+      let min = block_succs
+        .get(&block)
+        .into_iter()
+        .flatten()
+        .map(|&(succ, sep)| xs[succ] - sep)
+        .fold(f32::INFINITY, f32::min);
+      if min.is_finite() {
+        xs[block] = xs[block].max(min);
+      }
+    }
+
+    (0..count).map(|v| xs[root[v]]).collect()
+  }
+}
+
+/// Returns blocks so that each block comes after all blocks it depends on.
+fn post_order(blocks: &[usize], deps: &HashMap<usize, Vec<(usize, f32)>>) -> Vec<usize> {
+  let mut result = Vec::with_capacity(blocks.len());
+  let mut visited = HashSet::new();
+
+  for &block in blocks {
+    if !visited.insert(block) {
+      continue;
+    }
+
+    let mut stack = vec![(block, 0)];
+    while let Some((node, next)) = stack.last_mut() {
+      let node = *node;
+      let dep = deps.get(&node).and_then(|deps| deps.get(*next)).map(|&(dep, _)| dep);
+      *next += 1;
+
+      if let Some(dep) = dep {
+        if visited.insert(dep) {
+          stack.push((dep, 0));
+        }
+      } else {
+        result.push(node);
+        stack.pop();
+      }
+    }
+  }
+
+  result
+}
+
+/// Assigns each node to the layer one below its deepest predecessor.
+fn longest_path_layers(succs: &[Vec<usize>]) -> Vec<usize> {
+  let count = succs.len();
+  let mut in_degree = vec![0; count];
+  for tos in succs {
+    for &to in tos {
+      in_degree[to] += 1;
+    }
+  }
+
+  let mut layer = vec![0; count];
+  let mut queue = (0..count).filter(|&v| in_degree[v] == 0).collect::<Vec<_>>();
+  while let Some(v) = queue.pop() {
+    for &to in &succs[v] {
+      layer[to] = layer[to].max(layer[v] + 1);
+      in_degree[to] -= 1;
+      if in_degree[to] == 0 {
+        queue.push(to);
+      }
+    }
+  }
+
+  layer
+}
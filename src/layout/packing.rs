@@ -0,0 +1,208 @@
+//! Packing of disconnected trees.
+//!
+//! Each connected part of the graph is laid out on its own first.
+//! Then their bounding boxes are packed next to each other by [`ForestPacking`],
+//! and single nodes may be gathered into one grid.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use egui::{Pos2, Rect, Vec2, vec2};
+
+use crate::NodeId;
+
+use super::{ForestPacking, LayoutConfig, NodeDimensionProvider, SpanningForest};
+
+/// Box of nodes moved together, with offsets of nodes from its top-left corner.
+struct Item {
+  nodes: Vec<(NodeId, Vec2)>,
+  size: Vec2,
+}
+
+/// Moves connected parts of the laid out graph into places chosen by [`ForestPacking`].
+pub(super) fn pack_forest<P: NodeDimensionProvider>(
+  positions: &mut HashMap<NodeId, Pos2>,
+  forest: &SpanningForest<'_, P>,
+  config: &LayoutConfig,
+) {
+  if config.forest_packing == ForestPacking::Row && !config.singleton_grid {
+    return;
+  }
+
+  let size = |id: NodeId| {
+    // Sizes come swapped for horizontal orientations.
+    let (width, height) = forest.get_size(id);
+    if config.orientation.is_horizontal() { vec2(height, width) } else { vec2(width, height) }
+  };
+
+  let mut items = Vec::new();
+  let mut singletons = Vec::new();
+
+  for component in components(positions, forest) {
+    let rects = component.iter().map(|&id| Rect::from_min_size(positions[&id], size(id)));
+    let bounds = rects.fold(Rect::NOTHING, Rect::union);
+
+    if config.singleton_grid && component.len() == 1 {
+      singletons.push(component[0]);
+      continue;
+    }
+
+    items.push(Item {
+      nodes: component.iter().map(|&id| (id, positions[&id] - bounds.min)).collect(),
+      size: bounds.size(),
+    });
+  }
+
+  if !singletons.is_empty() {
+    items.push(grid(&singletons, size, config));
+  }
+
+  let gap = config.horizontal_spacing + config.subtree_spacing;
+  let places = match config.forest_packing {
+    ForestPacking::Row => row(&items, gap),
+    ForestPacking::Wrap => wrap(&items, gap, config.packing_aspect_ratio),
+    ForestPacking::Shelf => shelf(&items, gap, config.packing_aspect_ratio),
+  };
+
+  for (item, place) in items.iter().zip(places) {
+    for &(id, offset) in &item.nodes {
+      positions.insert(id, config.start_pos + place + offset);
+    }
+  }
+}
+
+/// Splits nodes into connected parts, ignoring directions of wires.
+///
+/// Parts are ordered by their first root in the forest.
+fn components<P: NodeDimensionProvider>(
+  positions: &HashMap<NodeId, Pos2>,
+  forest: &SpanningForest<'_, P>,
+) -> Vec<Vec<NodeId>> {
+  let mut neighbors: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+  for &node in positions.keys() {
+    for child in forest.provider.get_children(node) {
+      if child != node && positions.contains_key(&child) {
+        neighbors.entry(node).or_default().push(child);
+        neighbors.entry(child).or_default().push(node);
+      }
+    }
+  }
+
+  let mut assigned = HashSet::new();
+  let mut components = Vec::new();
+
+  for &root in &forest.roots {
+    if !positions.contains_key(&root) || !assigned.insert(root) {
+      continue;
+    }
+
+    let mut component = Vec::new();
+    let mut queue = VecDeque::from([root]);
+    while let Some(node) = queue.pop_front() {
+      component.push(node);
+      for &next in neighbors.get(&node).into_iter().flatten() {
+        if assigned.insert(next) {
+          queue.push_back(next);
+        }
+      }
+    }
+    components.push(component);
+  }
+
+  components
+}
+
+/// Arranges single nodes in a grid of equal cells, as close to the aspect ratio as possible.
+fn grid(nodes: &[NodeId], size: impl Fn(NodeId) -> Vec2, config: &LayoutConfig) -> Item {
+  let cell = nodes.iter().fold(Vec2::ZERO, |cell, &id| cell.max(size(id)))
+    + Vec2::splat(config.horizontal_spacing + config.subtree_spacing);
+
+  #[allow(clippy::cast_precision_loss)]
+  let count = nodes.len() as f32;
+  let columns = (count * config.packing_aspect_ratio * cell.y / cell.x).sqrt().ceil().max(1.0);
+  #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+  let columns = (columns as usize).min(nodes.len());
+  let rows = nodes.len().div_ceil(columns);
+
+  #[allow(clippy::cast_precision_loss)]
+  let nodes = nodes
+    .iter()
+    .enumerate()
+    .map(|(idx, &id)| (id, vec2((idx % columns) as f32 * cell.x, (idx / columns) as f32 * cell.y)))
+    .collect();
+
+  #[allow(clippy::cast_precision_loss)]
+  let size = vec2(columns as f32 * cell.x, rows as f32 * cell.y)
+    - Vec2::splat(config.horizontal_spacing + config.subtree_spacing);
+
+  Item { nodes, size }
+}
+
+/// Places items in one row.
+fn row(items: &[Item], gap: f32) -> Vec<Vec2> {
+  let mut x = 0.0;
+  items
+    .iter()
+    .map(|item| {
+      let place = vec2(x, 0.0);
+      x += item.size.x + gap;
+      place
+    })
+    .collect()
+}
+
+/// Returns width of rows for items to fill an area with the aspect ratio.
+fn row_width(items: &[Item], gap: f32, aspect_ratio: f32) -> f32 {
+  let area = items.iter().map(|item| (item.size.x + gap) * (item.size.y + gap)).sum::<f32>();
+  let widest = items.iter().map(|item| item.size.x).fold(0.0, f32::max);
+  (area * aspect_ratio.max(f32::EPSILON)).sqrt().max(widest)
+}
+
+/// Places items in rows in their order, starting a new row when one gets too wide.
+fn wrap(items: &[Item], gap: f32, aspect_ratio: f32) -> Vec<Vec2> {
+  let width = row_width(items, gap, aspect_ratio);
+
+  let mut cursor = Vec2::ZERO;
+  let mut row_height = 0.0f32;
+  items
+    .iter()
+    .map(|item| {
+      if cursor.x > 0.0 && cursor.x + item.size.x > width {
+        cursor = vec2(0.0, cursor.y + row_height + gap);
+        row_height = 0.0;
+      }
+      let place = cursor;
+      cursor.x += item.size.x + gap;
+      row_height = row_height.max(item.size.y);
+      place
+    })
+    .collect()
+}
+
+/// Packs items from the tallest into shelves, each item into the first shelf with room for it.
+fn shelf(items: &[Item], gap: f32, aspect_ratio: f32) -> Vec<Vec2> {
+  let width = row_width(items, gap, aspect_ratio);
+
+  let mut order = (0..items.len()).collect::<Vec<_>>();
+  order.sort_by(|&a, &b| items[b].size.y.total_cmp(&items[a].size.y));
+
+  // Shelves as `(y, height, used width)`.
+  let mut shelves: Vec<(f32, f32, f32)> = Vec::new();
+  let mut places = vec![Vec2::ZERO; items.len()];
+
+  for idx in order {
+    let size = items[idx].size;
+    let fits = shelves.iter().position(|(_, _, used)| *used + size.x <= width);
+
+    let shelf = fits.unwrap_or_else(|| {
+      let y = shelves.last().map_or(0.0, |&(y, height, _)| y + height + gap);
+      shelves.push((y, size.y, 0.0));
+      shelves.len() - 1
+    });
+
+    let (y, _, used) = &mut shelves[shelf];
+    places[idx] = vec2(*used, *y);
+    *used += size.x + gap;
+  }
+
+  places
+}
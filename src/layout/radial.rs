@@ -0,0 +1,201 @@
+//! Radial layout for wide and shallow trees.
+//!
+//! The root is placed at the center and each depth on a ring around it.
+//! Every node gets an angular wedge of its parent's wedge proportional to its number of leaves.
+//! Ring radii are chosen from node sizes, so nodes never overlap on a ring or between rings.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use egui::{Pos2, Rect, Vec2, pos2, vec2};
+
+use crate::NodeId;
+
+use super::{LayoutConfig, NodeDimensionProvider};
+
+/// Node of the tree being arranged by [`RadialTree`].
+struct RadialNode {
+  id: NodeId,
+  children: Vec<usize>,
+  depth: usize,
+  size: Vec2,
+  leaves: usize,
+  angle: f32,
+}
+
+impl RadialNode {
+  /// Radius of the circle around the node.
+  fn reach(&self) -> f32 {
+    self.size.length() / 2.0
+  }
+}
+
+/// Spanning tree of one root, nodes are stored in breadth-first order.
+struct RadialTree {
+  nodes: Vec<RadialNode>,
+}
+
+/// Arranges trees of the roots on rings, one disc next to another.
+///
+/// [`LayoutConfig::vertical_spacing`] separates rings and
+/// [`LayoutConfig::horizontal_spacing`] separates nodes on a ring and discs of different roots.
+/// [`LayoutConfig::orientation`] is ignored.
+pub(super) fn radial_layout<P: NodeDimensionProvider>(
+  roots: &[NodeId],
+  provider: &P,
+  config: &LayoutConfig,
+) -> HashMap<NodeId, Pos2> {
+  let mut positions = HashMap::new();
+  let mut visited = HashSet::new();
+  let mut x_offset = 0.0;
+
+  for &root in roots {
+    if visited.contains(&root) {
+      continue;
+    }
+
+    let mut tree = RadialTree::new(root, provider, config, &mut visited);
+    tree.assign_angles();
+    let radii = tree.ring_radii(config);
+
+    let rects = tree
+      .nodes
+      .iter()
+      .map(|node| {
+        let center = pos2(node.angle.cos(), node.angle.sin()) * radii[node.depth];
+        Rect::from_center_size(center, node.size)
+      })
+      .collect::<Vec<_>>();
+
+    let bounds = rects.iter().fold(Rect::NOTHING, |bounds, rect| bounds.union(*rect));
+    let offset = vec2(config.start_pos.x + x_offset, config.start_pos.y) - bounds.min.to_vec2();
+
+    for (node, rect) in tree.nodes.iter().zip(&rects) {
+      positions.insert(node.id, rect.min + offset);
+    }
+
+    x_offset += bounds.width() + config.horizontal_spacing;
+  }
+
+  positions
+}
+
+impl RadialTree {
+  /// Collects the spanning tree of the root by breadth-first search.
+  ///
+  /// Nodes already in `visited` are skipped, so each node is placed once.
+  fn new<P: NodeDimensionProvider>(
+    root: NodeId,
+    provider: &P,
+    config: &LayoutConfig,
+    visited: &mut HashSet<NodeId>,
+  ) -> Self {
+    let size = |id| {
+      // Sizes come swapped for horizontal orientations, the rings have no direction.
+      let (width, height) = provider.get_size(id);
+      if config.orientation.is_horizontal() { vec2(height, width) } else { vec2(width, height) }
+    };
+
+    visited.insert(root);
+    let mut nodes = vec![RadialNode {
+      id: root,
+      children: Vec::new(),
+      depth: 0,
+      size: size(root),
+      leaves: 1,
+      angle: 0.0,
+    }];
+
+    let mut queue = VecDeque::from([0]);
+    while let Some(parent) = queue.pop_front() {
+      for child in provider.get_children(nodes[parent].id) {
+        if !visited.insert(child) {
+          continue;
+        }
+
+        let index = nodes.len();
+        nodes.push(RadialNode {
+          id: child,
+          children: Vec::new(),
+          depth: nodes[parent].depth + 1,
+          size: size(child),
+          leaves: 1,
+          angle: 0.0,
+        });
+        nodes[parent].children.push(index);
+        queue.push_back(index);
+      }
+    }
+
+    // Children always follow their parent, so reverse order visits them first.
+    for index in (0..nodes.len()).rev() {
+      if !nodes[index].children.is_empty() {
+        nodes[index].leaves = nodes[index].children.iter().map(|&child| nodes[child].leaves).sum();
+      }
+    }
+
+    RadialTree { nodes }
+  }
+
+  /// Splits wedge of each node between its children by their leaf counts
+  /// and places every node in the middle of its wedge.
+  fn assign_angles(&mut self) {
+    // The first wedge starts at the top of the disc and wedges go clockwise.
+    let mut wedges = vec![(-FRAC_PI_2, TAU); self.nodes.len()];
+    self.nodes[0].angle = -FRAC_PI_2;
+
+    for parent in 0..self.nodes.len() {
+      let (mut start, span) = wedges[parent];
+      #[allow(clippy::cast_precision_loss)]
+      let per_leaf = span / self.nodes[parent].leaves as f32;
+
+      for idx in 0..self.nodes[parent].children.len() {
+        let child = self.nodes[parent].children[idx];
+        #[allow(clippy::cast_precision_loss)]
+        let child_span = per_leaf * self.nodes[child].leaves as f32;
+        wedges[child] = (start, child_span);
+        self.nodes[child].angle = start + child_span / 2.0;
+        start += child_span;
+      }
+    }
+  }
+
+  /// Returns radius of the ring for each depth.
+  ///
+  /// A ring is far enough from the previous one for node circles not to touch,
+  /// and large enough for neighboring nodes on it to fit with spacing between them.
+  fn ring_radii(&self, config: &LayoutConfig) -> Vec<f32> {
+    let depth = self.nodes.iter().map(|node| node.depth).max().unwrap_or(0);
+
+    // Breadth-first order keeps nodes of every ring sorted by angle.
+    let mut rings = vec![Vec::new(); depth + 1];
+    for (index, node) in self.nodes.iter().enumerate() {
+      rings[node.depth].push(index);
+    }
+
+    let mut radii = vec![0.0; depth + 1];
+    let mut prev_reach = self.nodes[0].reach();
+
+    for (depth, ring) in rings.iter().enumerate().skip(1) {
+      let reach = ring.iter().map(|&index| self.nodes[index].reach()).fold(0.0, f32::max);
+      let mut radius = radii[depth - 1] + prev_reach + reach + config.vertical_spacing;
+
+      if ring.len() > 1 {
+        for (idx, &a) in ring.iter().enumerate() {
+          let b = ring[(idx + 1) % ring.len()];
+          let (a, b) = (&self.nodes[a], &self.nodes[b]);
+
+          let delta = (b.angle - a.angle).rem_euclid(TAU);
+          let delta = delta.min(TAU - delta);
+          let need = a.reach() + b.reach() + config.horizontal_spacing;
+          radius = radius.max(need / (2.0 * (delta / 2.0).sin()));
+        }
+      }
+
+      radii[depth] = radius;
+      prev_reach = reach;
+    }
+
+    radii
+  }
+}
@@ -0,0 +1,212 @@
+//! Result of a layout with geometry derived from node positions.
+
+use std::collections::{HashMap, HashSet};
+
+use egui::{Pos2, Rect, Vec2, vec2};
+
+use crate::{InPinId, NodeId, OutPinId, Treeize};
+
+use super::{LayoutConfig, LayoutGraph, LayoutOrientation};
+
+/// Result of [`layout_tree`](super::layout_tree).
+///
+/// Besides positions it describes the arranged graph, so it can be drawn,
+/// fitted into a viewport or exported without walking the graph again.
+/// Everything except `positions` and `back_edges` covers only nodes that took part in the layout,
+/// nodes hidden by [`LayoutConfig::fold_collapsed`] are left out.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::{InPinId, OutPinId, Treeize};
+/// # use egui_treeize::layout::{LayoutConfig, layout_tree};
+/// let mut treeize = Treeize::new();
+/// let a = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let b = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// let c = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+/// treeize.connect(OutPinId { node: a, output: 0 }, InPinId { node: b, input: 0 });
+/// treeize.connect(OutPinId { node: b, output: 0 }, InPinId { node: c, input: 0 });
+/// treeize.connect(OutPinId { node: c, output: 0 }, InPinId { node: a, input: 0 });
+///
+/// let result = layout_tree(&treeize, LayoutConfig::default(), |_| true, |_| true, None);
+///
+/// assert_eq!(result.positions.len(), 3);
+/// assert_eq!(result.back_edges.len(), 1);
+/// assert_eq!(result.roots, [a]);
+/// assert_eq!((result.depths[&a], result.depths[&c]), (0, 2));
+/// assert_eq!(result.subtree_bounds[&a], result.bounds);
+/// assert!(result.subtree_bounds[&b].contains_rect(result.subtree_bounds[&c]));
+/// assert_eq!(result.wires.len(), 3);
+///
+/// // The back edge leaves `c` downwards and goes around to enter `a` from above.
+/// let back = &result.wires[&(OutPinId { node: c, output: 0 }, InPinId { node: a, input: 0 })];
+/// assert_eq!(back.len(), 6);
+/// assert!(back[1].y > back[0].y && back[4].y < back[5].y);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LayoutResult {
+  /// Calculated position (top-left corner) of each node.
+  pub positions: HashMap<NodeId, Pos2>,
+
+  /// Wires that close cycles and were left out when arranging nodes.
+  /// They point against the direction of the layout.
+  pub back_edges: HashSet<(OutPinId, InPinId)>,
+
+  /// Roots of the spanning forest the nodes were arranged along,
+  /// see [`LayoutGraph::roots`].
+  pub roots: Vec<NodeId>,
+
+  /// Depth of each node in the spanning forest, zero for roots.
+  pub depths: HashMap<NodeId, usize>,
+
+  /// Rectangle around each node and all its descendants in the spanning forest.
+  pub subtree_bounds: HashMap<NodeId, Rect>,
+
+  /// Rectangle around all nodes, [`Rect::NOTHING`] if there are none.
+  pub bounds: Rect,
+
+  /// Suggested path of each wire, from the output side of the parent
+  /// to the input side of the child.
+  ///
+  /// Paths are orthogonal and turn halfway between the nodes along
+  /// [`LayoutConfig::orientation`], or right above children that are further away,
+  /// like rows wrapped by [`LayoutConfig::sibling_wrapping`].
+  /// Wires that point against the orientation, like back edges, go around both nodes.
+  pub wires: HashMap<(OutPinId, InPinId), Vec<Pos2>>,
+}
+
+impl Default for LayoutResult {
+  fn default() -> Self {
+    LayoutResult {
+      positions: HashMap::new(),
+      back_edges: HashSet::new(),
+      roots: Vec::new(),
+      depths: HashMap::new(),
+      subtree_bounds: HashMap::new(),
+      bounds: Rect::NOTHING,
+      wires: HashMap::new(),
+    }
+  }
+}
+
+impl LayoutResult {
+  /// Describes the graph with `positions` applied.
+  /// Nodes missing in `positions` stay where they are.
+  pub(super) fn new<T>(
+    graph: &LayoutGraph<'_>,
+    treeize: &Treeize<T>,
+    positions: HashMap<NodeId, Pos2>,
+    config: &LayoutConfig,
+  ) -> Self {
+    let rect = |node: NodeId| {
+      let pos = positions.get(&node).copied().unwrap_or_else(|| graph.position(node));
+      Rect::from_min_size(pos, graph.size(node))
+    };
+
+    // Roots come before their descendants, so walking the order backwards
+    // visits children before parents.
+    let mut depths = HashMap::new();
+    let mut order = Vec::with_capacity(graph.nodes().len());
+    for &root in graph.roots() {
+      depths.insert(root, 0);
+      order.push(root);
+    }
+    let mut next = 0;
+    while let Some(&node) = order.get(next) {
+      let depth = depths[&node] + 1;
+      for &child in graph.tree_children(node) {
+        depths.insert(child, depth);
+        order.push(child);
+      }
+      next += 1;
+    }
+
+    let mut subtree_bounds = HashMap::new();
+    for &node in order.iter().rev() {
+      let bounds = graph
+        .tree_children(node)
+        .iter()
+        .fold(rect(node), |bounds, child| bounds.union(subtree_bounds[child]));
+      subtree_bounds.insert(node, bounds);
+    }
+
+    let bounds = graph.nodes().iter().fold(Rect::NOTHING, |bounds, &node| bounds.union(rect(node)));
+
+    let router = WireRouter::new(config);
+    let wires = treeize
+      .sorted_wires()
+      .filter(|(out_pin, in_pin)| graph.children(out_pin.node).contains(&in_pin.node))
+      .map(|wire| (wire, router.route(rect(wire.0.node), rect(wire.1.node))))
+      .collect();
+
+    LayoutResult {
+      back_edges: graph.back_wires(treeize),
+      positions,
+      roots: graph.roots().to_vec(),
+      depths,
+      subtree_bounds,
+      bounds,
+      wires,
+    }
+  }
+}
+
+/// Draws orthogonal wires between node rectangles along the orientation of the layout.
+struct WireRouter {
+  /// Unit vector from parents to children.
+  along: Vec2,
+  /// Unit vector along levels.
+  across: Vec2,
+  /// Distance kept from nodes by wires that go around them.
+  clearance: Vec2,
+}
+
+impl WireRouter {
+  fn new(config: &LayoutConfig) -> Self {
+    let (along, across) = match config.orientation {
+      LayoutOrientation::TopToBottom => (vec2(0.0, 1.0), vec2(1.0, 0.0)),
+      LayoutOrientation::BottomToTop => (vec2(0.0, -1.0), vec2(1.0, 0.0)),
+      LayoutOrientation::LeftToRight => (vec2(1.0, 0.0), vec2(0.0, 1.0)),
+      LayoutOrientation::RightToLeft => (vec2(-1.0, 0.0), vec2(0.0, 1.0)),
+    };
+    let clearance = vec2(config.horizontal_spacing, config.vertical_spacing) / 2.0;
+    WireRouter { along, across, clearance }
+  }
+
+  /// Returns point with the given coordinates along and across the levels.
+  fn point(&self, along: f32, across: f32) -> Pos2 {
+    (self.along * along + self.across * across).to_pos2()
+  }
+
+  /// Returns path from the output side of `from` to the input side of `to`.
+  fn route(&self, from: Rect, to: Rect) -> Vec<Pos2> {
+    let (along, across) = (self.along, self.across);
+    let start = from.center() + from.size() * along / 2.0;
+    let end = to.center() - to.size() * along / 2.0;
+    let (start_along, start_across) = (start.to_vec2().dot(along), start.to_vec2().dot(across));
+    let (end_along, end_across) = (end.to_vec2().dot(along), end.to_vec2().dot(across));
+
+    if end_along > start_along {
+      #[allow(clippy::float_cmp)]
+      if start_across == end_across {
+        return vec![start, end];
+      }
+      let middle = f32::max(f32::midpoint(start_along, end_along), end_along - self.clearance.y);
+      return vec![start, self.point(middle, start_across), self.point(middle, end_across), end];
+    }
+
+    // Leave the parent, pass both nodes on the far side across levels and enter the child.
+    let side =
+      f32::max(from.max.to_vec2().dot(across), to.max.to_vec2().dot(across)) + self.clearance.x;
+    let out = start_along + self.clearance.y;
+    let into = end_along - self.clearance.y;
+    vec![
+      start,
+      self.point(out, start_across),
+      self.point(out, side),
+      self.point(into, side),
+      self.point(into, end_across),
+      end,
+    ]
+  }
+}
@@ -0,0 +1,393 @@
+//! Incremental layout of a single subtree.
+//!
+//! The subtree is arranged anew with its root left in place.
+//! Then, level by level up to the root of the tree, siblings are shifted apart or together
+//! so that their clearance to the changed subtree stays the same as before,
+//! and parents keep their offset from the middle of their children.
+//! Nodes outside of the subtree's tree are never moved.
+
+use std::collections::{BTreeMap, HashMap};
+
+use egui::{Pos2, Rect, Vec2, pos2, vec2};
+
+use crate::NodeId;
+
+use super::graph::OrientedForest;
+use super::{LayoutConfig, LayoutOrientation, NodeDimensionProvider, sort_key, tidy_layout};
+
+/// Returns new positions of nodes moved by re-layout of the subtree of `node`.
+///
+/// `current` returns position of a node before the re-layout.
+pub(super) fn relayout_subtree_positions(
+  node: NodeId,
+  forest: &OrientedForest<'_, '_>,
+  config: &LayoutConfig,
+  current: impl Fn(NodeId) -> Pos2,
+) -> HashMap<NodeId, Pos2> {
+  let mut moved = tidy_layout(&[node], forest, config);
+  let offset = current(node) - moved[&node];
+  for pos in moved.values_mut() {
+    *pos += offset;
+  }
+
+  let horizontal = config.orientation.is_horizontal();
+
+  // Rect of a node in top-to-bottom space, x across levels and y along them.
+  let rect = |id: NodeId, pos: Pos2| {
+    let (width, height) = forest.get_size(id);
+    let pos = if horizontal { pos2(pos.y, pos.x) } else { pos };
+    Rect::from_min_size(pos, vec2(width, height))
+  };
+  let across = |delta: f32| if horizontal { vec2(0.0, delta) } else { vec2(delta, 0.0) };
+
+  // A node that grew towards its parent moves away from it with the whole subtree.
+  if let Some(parent) = forest.parent(node) {
+    let parent_rect = rect(parent, current(parent));
+    let node_rect = rect(node, moved[&node]);
+    let spacing = config.vertical_spacing;
+
+    let deficit = match config.orientation {
+      LayoutOrientation::TopToBottom | LayoutOrientation::LeftToRight => {
+        (parent_rect.max.y + spacing - node_rect.min.y).max(0.0)
+      }
+      LayoutOrientation::BottomToTop | LayoutOrientation::RightToLeft => {
+        -(node_rect.max.y + spacing - parent_rect.min.y).max(0.0)
+      }
+    };
+    let along = if horizontal { vec2(deficit, 0.0) } else { vec2(0.0, deficit) };
+    for pos in moved.values_mut() {
+      *pos += along;
+    }
+  }
+
+  // Contours of the changed subtree before and after the change,
+  // they grow to the subtree of each ancestor.
+  let mut old = Outline::new(moved.keys().map(|&id| rect(id, current(id))));
+  let mut new = Outline::new(moved.iter().map(|(&id, &pos)| rect(id, pos)));
+  let mut current_node = node;
+
+  while let Some(parent) = forest.parent(current_node) {
+    // Siblings are ordered by where they are now, not by the order of wires.
+    let mut siblings = forest.children(parent).to_vec();
+    siblings.sort_by(|&a, &b| {
+      let a = rect(a, current(a)).center().x;
+      let b = rect(b, current(b)).center().x;
+      a.total_cmp(&b)
+    });
+    let idx = siblings.iter().position(|&child| child == current_node).unwrap_or(0);
+
+    let left = collect_subtrees(&siblings[..idx], forest);
+    let right = collect_subtrees(&siblings[idx + 1..], forest);
+
+    // Right siblings keep their clearance to the changed subtree.
+    let right_rects = right.iter().map(|&id| rect(id, current(id)));
+    let delta = clearance_change(&old, &new, right_rects, Side::Right, config);
+    shift(&mut moved, &right, across(delta), &current);
+
+    // Left siblings too, but on the other side.
+    let left_rects = left.iter().map(|&id| rect(id, current(id)));
+    let delta = clearance_change(&old, &new, left_rects, Side::Left, config);
+    shift(&mut moved, &left, across(-delta), &current);
+
+    // Parent keeps its offset from the middle of the first and last child.
+    let middle = |pos: &dyn Fn(NodeId) -> Pos2| {
+      let first = siblings[0];
+      let last = siblings[siblings.len() - 1];
+      f32::midpoint(rect(first, pos(first)).center().x, rect(last, pos(last)).center().x)
+    };
+    let old_middle = middle(&current);
+    let new_middle = middle(&|id| moved.get(&id).copied().unwrap_or_else(|| current(id)));
+    shift(&mut moved, &[parent], across(new_middle - old_middle), &current);
+
+    // Only the nodes that join the subtree are added to its contours.
+    let joined = left.into_iter().chain(right).chain([parent]).collect::<Vec<_>>();
+    old.merge(joined.iter().map(|&id| rect(id, current(id))));
+    new.merge(
+      joined.iter().map(|&id| rect(id, moved.get(&id).copied().unwrap_or_else(|| current(id)))),
+    );
+    current_node = parent;
+  }
+
+  moved.retain(|&id, pos| *pos != current(id));
+  moved
+}
+
+/// Collects all nodes of the subtrees of the roots.
+fn collect_subtrees(roots: &[NodeId], forest: &OrientedForest<'_, '_>) -> Vec<NodeId> {
+  let mut nodes = roots.to_vec();
+  let mut next = 0;
+  while next < nodes.len() {
+    nodes.extend(forest.children(nodes[next]));
+    next += 1;
+  }
+  nodes
+}
+
+/// Moves the nodes by `delta` from their latest position.
+fn shift(
+  moved: &mut HashMap<NodeId, Pos2>,
+  nodes: &[NodeId],
+  delta: Vec2,
+  current: &impl Fn(NodeId) -> Pos2,
+) {
+  if delta == Vec2::ZERO {
+    return;
+  }
+
+  for &id in nodes {
+    let pos = moved.entry(id).or_insert_with(|| current(id));
+    *pos += delta;
+  }
+}
+
+/// Side of the changed subtree where other nodes are.
+#[derive(Clone, Copy)]
+enum Side {
+  Left,
+  Right,
+}
+
+impl Side {
+  fn opposite(self) -> Side {
+    match self {
+      Side::Left => Side::Right,
+      Side::Right => Side::Left,
+    }
+  }
+
+  /// Gap between a rect of the changed subtree and a rect on this side.
+  fn gap(self, subtree: &Rect, other: &Rect) -> f32 {
+    match self {
+      Side::Left => subtree.min.x - other.max.x,
+      Side::Right => other.min.x - subtree.max.x,
+    }
+  }
+
+  /// Edge of a rect facing this side, larger is closer to it.
+  fn edge(self, rect: &Rect) -> f32 {
+    match self {
+      Side::Left => -rect.min.x,
+      Side::Right => rect.max.x,
+    }
+  }
+}
+
+/// Contours of the changed subtree on both sides.
+struct Outline {
+  left: Contour,
+  right: Contour,
+}
+
+impl Outline {
+  fn new(rects: impl Iterator<Item = Rect>) -> Self {
+    let rects = rects.collect::<Vec<_>>();
+    Outline { left: Contour::new(&rects, Side::Left), right: Contour::new(&rects, Side::Right) }
+  }
+
+  fn facing(&self, side: Side) -> &Contour {
+    match side {
+      Side::Left => &self.left,
+      Side::Right => &self.right,
+    }
+  }
+
+  /// Adds rects of nodes that join the subtree.
+  fn merge(&mut self, rects: impl Iterator<Item = Rect>) {
+    let rects = rects.collect::<Vec<_>>();
+    self.left.merge(&Contour::new(&rects, Side::Left));
+    self.right.merge(&Contour::new(&rects, Side::Right));
+  }
+}
+
+/// Edge of a group of rects facing one side, in pieces along y.
+///
+/// Each piece spans the part of y where one rect is the closest to the side.
+struct Contour {
+  facing: Side,
+  /// Pieces by their top.
+  pieces: BTreeMap<u32, Piece>,
+}
+
+#[derive(Clone, Copy)]
+struct Piece {
+  top: f32,
+  bottom: f32,
+  rect: Rect,
+}
+
+impl Contour {
+  fn new(rects: &[Rect], facing: Side) -> Self {
+    let mut contour = Contour { facing, pieces: BTreeMap::new() };
+
+    let mut bounds = rects.iter().flat_map(|rect| [rect.min.y, rect.max.y]).collect::<Vec<_>>();
+    bounds.sort_by(f32::total_cmp);
+    bounds.dedup();
+    if bounds.len() < 2 {
+      return contour;
+    }
+
+    let closest = cover(&bounds, rects, |rect| facing.edge(rect));
+    contour.extend(
+      bounds
+        .windows(2)
+        .zip(closest)
+        .filter_map(|(span, rect)| Some(Piece { top: span[0], bottom: span[1], rect: rect? })),
+    );
+    contour
+  }
+
+  /// Inserts pieces ordered by y, joining neighbours of the same rect.
+  fn extend(&mut self, pieces: impl IntoIterator<Item = Piece>) {
+    let mut last: Option<Piece> = None;
+    for piece in pieces {
+      // Pieces are split at the same bounds, so touching ones share exactly the same y.
+      #[allow(clippy::float_cmp)]
+      if let Some(last) = &mut last
+        && last.rect == piece.rect
+        && last.bottom == piece.top
+      {
+        last.bottom = piece.bottom;
+        continue;
+      }
+      if let Some(done) = last.replace(piece) {
+        self.pieces.insert(sort_key(done.top), done);
+      }
+    }
+    if let Some(done) = last {
+      self.pieces.insert(sort_key(done.top), done);
+    }
+  }
+
+  /// Returns pieces that overlap y between `top` and `bottom`, ordered by y.
+  fn overlapping(&self, top: f32, bottom: f32) -> impl Iterator<Item = &Piece> {
+    let start = self
+      .pieces
+      .range(..=sort_key(top))
+      .next_back()
+      .filter(|(_, piece)| piece.bottom > top)
+      .map_or(sort_key(top), |(&key, _)| key);
+    self.pieces.range(start..).map(|(_, piece)| piece).take_while(move |piece| piece.top < bottom)
+  }
+
+  /// Adds pieces of `other`, facing the same side, keeping the closest rects.
+  ///
+  /// Only pieces of this contour along the span of `other` are visited.
+  fn merge(&mut self, other: &Contour) {
+    let (Some(first), Some(last)) = (other.pieces.values().next(), other.pieces.values().last())
+    else {
+      return;
+    };
+
+    let mine = self.overlapping(first.top, last.bottom).copied().collect::<Vec<_>>();
+    for piece in &mine {
+      self.pieces.remove(&sort_key(piece.top));
+    }
+
+    let mut bounds =
+      mine.iter().chain(other.pieces.values()).flat_map(|p| [p.top, p.bottom]).collect::<Vec<_>>();
+    bounds.sort_by(f32::total_cmp);
+    bounds.dedup();
+
+    let mut mine = mine.iter().peekable();
+    let mut theirs = other.pieces.values().peekable();
+    let mut merged = Vec::new();
+    for span in bounds.windows(2) {
+      let top = span[0];
+      while mine.next_if(|piece| piece.bottom <= top).is_some() {}
+      while theirs.next_if(|piece| piece.bottom <= top).is_some() {}
+
+      let covering =
+        |piece: Option<&&Piece>| piece.filter(|piece| piece.top <= top).map(|p| p.rect);
+      let closest = match (covering(mine.peek()), covering(theirs.peek())) {
+        (Some(a), Some(b)) => Some(if self.facing.edge(&b) > self.facing.edge(&a) { b } else { a }),
+        (a, b) => a.or(b),
+      };
+      if let Some(rect) = closest {
+        merged.push(Piece { top, bottom: span[1], rect });
+      }
+    }
+    self.extend(merged);
+  }
+}
+
+/// Returns how far `others` must move away from the changed subtree
+/// to keep the clearance they had to it before the change.
+///
+/// The clearance never drops below the spacing, so stale positions of nodes
+/// that were just added or expanded can't pull others into the subtree.
+/// Others that did not face the subtree before are only pushed away.
+fn clearance_change(
+  old: &Outline,
+  new: &Outline,
+  others: impl Iterator<Item = Rect>,
+  side: Side,
+  config: &LayoutConfig,
+) -> f32 {
+  let others = Contour::new(&others.collect::<Vec<_>>(), side.opposite());
+  if others.pieces.is_empty() {
+    return 0.0;
+  }
+
+  let Some(new) = min_gap(new.facing(side), &others, side) else {
+    return 0.0;
+  };
+
+  match min_gap(old.facing(side), &others, side) {
+    Some(old) => old.max(config.horizontal_spacing) - new,
+    None => (config.horizontal_spacing - new).max(0.0),
+  }
+}
+
+/// Returns the smallest gap between the contour of the subtree and the contour of others
+/// where they overlap along y.
+///
+/// Only pieces of the subtree along the pieces of others are visited.
+fn min_gap(subtree: &Contour, others: &Contour, side: Side) -> Option<f32> {
+  others
+    .pieces
+    .values()
+    .flat_map(|other| {
+      subtree.overlapping(other.top, other.bottom).map(|piece| side.gap(&piece.rect, &other.rect))
+    })
+    .min_by(f32::total_cmp)
+}
+
+/// For each interval between `bounds`, returns the covering rect with the largest `key`.
+fn cover(bounds: &[f32], rects: &[Rect], key: impl Fn(&Rect) -> f32) -> Vec<Option<Rect>> {
+  let mut sorted = rects.iter().collect::<Vec<_>>();
+  sorted.sort_by(|a, b| key(b).total_cmp(&key(a)));
+
+  let count = bounds.len() - 1;
+  let mut result = vec![None; count];
+
+  // Rects are visited from the largest key, so each interval takes the first rect covering it.
+  // `next` skips intervals that are already taken.
+  let mut next = (0..=count).collect::<Vec<_>>();
+  for rect in sorted {
+    let start = bounds.partition_point(|&y| y < rect.min.y);
+    let end = bounds.partition_point(|&y| y < rect.max.y);
+
+    let mut idx = find_free(&mut next, start);
+    while idx < end {
+      result[idx] = Some(*rect);
+      next[idx] = idx + 1;
+      idx = find_free(&mut next, idx + 1);
+    }
+  }
+
+  result
+}
+
+/// Returns the first interval from `idx` that is not taken, compressing the path.
+fn find_free(next: &mut [usize], mut idx: usize) -> usize {
+  let mut free = idx;
+  while next[free] != free {
+    free = next[free];
+  }
+  while next[idx] != free {
+    let up = next[idx];
+    next[idx] = free;
+    idx = up;
+  }
+  free
+}
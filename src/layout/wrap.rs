@@ -0,0 +1,95 @@
+//! Wrapping of wide sibling sets into rows.
+//!
+//! Subtrees of the wrapped children are arranged on their own and packed into rows,
+//! each row split into halves that meet below the middle of the parent.
+//! The rows become a single node of the parent's [`TidyTree`].
+
+use std::collections::HashSet;
+
+use egui::{Rect, Vec2, pos2, vec2};
+
+use crate::NodeId;
+
+use super::{LayoutConfig, NodeDimensionProvider, SiblingWrapping, TidyTree};
+
+/// Children of a parent arranged in rows.
+pub(super) struct Block {
+  /// Size of the rows, including spacing like sizes of tidy nodes.
+  pub size: Vec2,
+
+  /// Rects of nodes including spacing, relative to the top-left corner of the block.
+  pub nodes: Vec<(NodeId, Rect)>,
+}
+
+/// Returns how many of the `children` go into one row, if they are wrapped.
+pub(super) fn row_length<P: NodeDimensionProvider>(
+  children: &[NodeId],
+  provider: &P,
+  config: &LayoutConfig,
+) -> Option<usize> {
+  if children.len() <= config.max_siblings {
+    return None;
+  }
+
+  match config.sibling_wrapping {
+    SiblingWrapping::Off => None,
+    SiblingWrapping::Rows => Some(config.max_siblings.max(1)),
+    SiblingWrapping::LeafColumn => {
+      let leaves = children.iter().all(|&child| provider.get_children(child).is_empty());
+      leaves.then_some(2)
+    }
+  }
+}
+
+/// Arranges subtrees of `children` and packs them into rows of `row_length`.
+///
+/// In each row the first half of the subtrees ends and the second half starts
+/// in the middle of the block.
+pub(super) fn wrap<P: NodeDimensionProvider>(
+  children: Vec<NodeId>,
+  row_length: usize,
+  provider: &P,
+  config: &LayoutConfig,
+  visited: &mut HashSet<NodeId>,
+) -> Block {
+  let subtrees = children
+    .into_iter()
+    .map(|child| {
+      let mut tree = TidyTree::new(child, provider, config, visited);
+      tree.layout();
+      let nodes = tree.placed();
+      let bounds = nodes.iter().fold(Rect::NOTHING, |bounds, (_, rect)| bounds.union(*rect));
+      (nodes, bounds)
+    })
+    .collect::<Vec<_>>();
+
+  let rows = subtrees.chunks(row_length).collect::<Vec<_>>();
+  let half_width = |half: &[(Vec<(NodeId, Rect)>, Rect)]| {
+    half.iter().map(|(_, bounds)| bounds.width()).sum::<f32>()
+  };
+
+  let middle = rows
+    .iter()
+    .map(|row| {
+      let (first, second) = row.split_at(row.len().div_ceil(2));
+      f32::max(half_width(first), half_width(second))
+    })
+    .fold(0.0, f32::max);
+
+  let mut nodes = Vec::new();
+  let mut y = 0.0;
+  for row in rows {
+    let mut x = middle - half_width(&row[..row.len().div_ceil(2)]);
+    let mut height = 0.0f32;
+
+    for (subtree, bounds) in row {
+      let offset = pos2(x, y) - bounds.min;
+      nodes.extend(subtree.iter().map(|&(node, rect)| (node, rect.translate(offset))));
+      x += bounds.width();
+      height = height.max(bounds.height());
+    }
+    y += height;
+  }
+
+  Block { size: vec2(middle * 2.0, y), nodes }
+}
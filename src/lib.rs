@@ -9,12 +9,23 @@
 #![warn(clippy::pedantic, clippy::dbg_macro, clippy::must_use_candidate)]
 #![allow(clippy::range_plus_one, clippy::inline_always, clippy::use_self)]
 
+pub mod clipboard;
+#[cfg(feature = "serde")]
+pub mod doc;
+#[cfg(feature = "fs")]
+pub mod fs;
+#[cfg(feature = "graphml")]
+pub mod graphml;
 pub mod layout;
 pub mod ui;
 
+use std::collections::VecDeque;
 use std::ops::{Index, IndexMut};
 
-use egui::{Pos2, ahash::HashSet};
+use egui::{
+  Pos2,
+  ahash::{HashMap, HashSet},
+};
 use slab::Slab;
 
 impl<T> Default for Treeize<T> {
@@ -76,7 +87,7 @@ pub struct InPinId {
 /// Nodes may support multiple connections to the same input or output.
 /// But duplicate connections between same input and the same output are not allowed.
 /// Attempt to insert existing connection will be ignored.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Wire {
   out_pin: OutPinId,
@@ -184,6 +195,12 @@ impl Wires {
   fn iter(&self) -> impl Iterator<Item = Wire> + '_ {
     self.wires.iter().copied()
   }
+
+  fn sorted(&self) -> Vec<Wire> {
+    let mut wires = self.iter().collect::<Vec<_>>();
+    wires.sort_unstable();
+    wires
+  }
 }
 
 /// Treeize is generic node-graph container.
@@ -254,6 +271,93 @@ impl<T> Treeize<T> {
     self.nodes[node.0].open = open;
   }
 
+  /// Returns nodes hidden in subtrees of collapsed nodes,
+  /// each mapped to the closest collapsed node that hides it.
+  ///
+  /// A node is hidden when every path to it from a root goes through a collapsed node.
+  /// Collapsed nodes themselves stay visible unless they are hidden by another one.
+  /// Nodes reachable only through cycles are treated as if the first of them was a root.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use egui_treeize::{InPinId, OutPinId, Treeize};
+  /// let mut treeize = Treeize::new();
+  /// let root = treeize.insert_node_collapsed(egui::pos2(0.0, 0.0), ());
+  /// let child = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+  /// let grandchild = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+  /// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: child, input: 0 });
+  /// treeize.connect(OutPinId { node: child, output: 0 }, InPinId { node: grandchild, input: 0 });
+  ///
+  /// let folded = treeize.folded_nodes();
+  /// assert_eq!(folded.len(), 2);
+  /// assert_eq!(folded[&grandchild], root);
+  ///
+  /// treeize.open_node(root, true);
+  /// assert!(treeize.folded_nodes().is_empty());
+  /// ```
+  #[must_use]
+  pub fn folded_nodes(&self) -> HashMap<NodeId, NodeId> {
+    let mut children = HashMap::<NodeId, Vec<NodeId>>::default();
+    let mut has_parent = HashSet::default();
+    for wire in self.wires.sorted() {
+      children.entry(wire.out_pin.node).or_default().push(wire.in_pin.node);
+      if wire.out_pin.node != wire.in_pin.node {
+        has_parent.insert(wire.in_pin.node);
+      }
+    }
+    let children_of = |node: NodeId| children.get(&node).map_or(&[][..], Vec::as_slice);
+
+    // Visits nodes from `start`, only going through open nodes if `open_only` is set.
+    let walk = |start: NodeId, open_only: bool, visited: &mut HashSet<NodeId>| {
+      if !visited.insert(start) {
+        return;
+      }
+      let mut stack = vec![start];
+      while let Some(node) = stack.pop() {
+        if open_only && !self.nodes[node.0].open {
+          continue;
+        }
+        stack.extend(children_of(node).iter().filter(|&&child| visited.insert(child)));
+      }
+    };
+
+    let mut reached = HashSet::default();
+    let mut visible = HashSet::default();
+
+    let roots = self.nodes.iter().map(|(idx, _)| NodeId(idx));
+    for root in roots.clone().filter(|node| !has_parent.contains(node)) {
+      walk(root, false, &mut reached);
+      walk(root, true, &mut visible);
+    }
+    for root in roots {
+      if !reached.contains(&root) {
+        walk(root, false, &mut reached);
+        walk(root, true, &mut visible);
+      }
+    }
+
+    // Hidden nodes are reached first from the closest visible collapsed node.
+    let mut folded = HashMap::default();
+    let mut queue = self
+      .nodes
+      .iter()
+      .filter(|(idx, node)| !node.open && visible.contains(&NodeId(*idx)))
+      .map(|(idx, _)| (NodeId(idx), NodeId(idx)))
+      .collect::<VecDeque<_>>();
+
+    while let Some((node, owner)) = queue.pop_front() {
+      for &child in children_of(node) {
+        if !visible.contains(&child) && !folded.contains_key(&child) {
+          folded.insert(child, owner);
+          queue.push_back((child, owner));
+        }
+      }
+    }
+
+    folded
+  }
+
   /// Removes a node from the Treeize.
   /// Returns the node if it was removed.
   ///
@@ -437,10 +541,36 @@ impl<T> Treeize<T> {
   }
 
   /// Iterates over wires.
+  ///
+  /// Order of wires is unspecified and may differ between runs,
+  /// use [`Treeize::sorted_wires`] when it matters.
   pub fn wires(&self) -> impl Iterator<Item = (OutPinId, InPinId)> + '_ {
     self.wires.iter().map(|wire| (wire.out_pin, wire.in_pin))
   }
 
+  /// Iterates over wires ordered by output pin, then by input pin.
+  ///
+  /// Wires of one output pin come in the order of their children's [`NodeId`]s,
+  /// which is the order children are laid out in.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use egui_treeize::{InPinId, OutPinId, Treeize};
+  /// let mut treeize = Treeize::new();
+  /// let root = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+  /// let first = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+  /// let second = treeize.insert_node(egui::pos2(0.0, 0.0), ());
+  /// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: second, input: 0 });
+  /// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: first, input: 0 });
+  ///
+  /// let children = treeize.sorted_wires().map(|(_, in_pin)| in_pin.node).collect::<Vec<_>>();
+  /// assert_eq!(children, [first, second]);
+  /// ```
+  pub fn sorted_wires(&self) -> impl Iterator<Item = (OutPinId, InPinId)> + '_ {
+    self.wires.sorted().into_iter().map(|wire| (wire.out_pin, wire.in_pin))
+  }
+
   /// Returns input pin of the node.
   #[must_use]
   pub fn in_pin(&self, pin: InPinId) -> InPin {
@@ -1,6 +1,9 @@
 //! This module provides functionality for showing [`Treeize`] graph in [`Ui`].
 
-use std::{collections::HashMap, hash::Hash};
+use std::{
+  collections::{HashMap, HashSet},
+  hash::Hash,
+};
 
 use egui::{
   Align, Color32, CornerRadius, Frame, Id, LayerId, Layout, Margin, Modifiers, PointerButton, Pos2,
@@ -16,11 +19,15 @@ use egui_scale::EguiScale;
 
 use crate::{
   InPin, InPinId, Node, NodeId, OutPin, OutPinId, Treeize,
-  layout::{LayoutConfig, layout_with_viewer},
+  layout::{ForceSimulation, LayoutAlgorithm, LayoutConfig, TreeLayout, layout_with_viewer_using},
   ui::wire::WireId,
 };
 
+mod animation;
+mod auto_layout;
 mod background_pattern;
+#[cfg(feature = "serde")]
+mod clipboard;
 mod pin;
 mod scale;
 pub(crate) mod state;
@@ -28,15 +35,19 @@ mod viewer;
 mod wire;
 
 use self::{
+  animation::NodeAnimations,
+  auto_layout::{AutoLayoutState, fingerprint},
   pin::AnyPin,
-  state::{NewWires, NodeState, TreeizeState},
-  wire::{draw_wire, hit_wire, pick_wire_style},
+  state::{NewWires, NodeState, TreeizeState, load_back_wires, set_back_wires},
+  wire::{WireFlow, dash_wire_shapes, draw_wire, hit_wire, pick_wire_style},
 };
 
 pub use self::{
+  animation::LayoutEasing,
+  auto_layout::AutoLayout,
   background_pattern::{BackgroundPattern, Grid},
   pin::{AnyPins, PinInfo, PinShape, PinWireInfo, TreeizePin},
-  state::get_selected_nodes,
+  state::{TreeizeViewState, get_selected_nodes},
   viewer::TreeizeViewer,
   wire::{WireLayer, WireStyle},
 };
@@ -58,6 +69,125 @@ pub enum NodeLayoutKind {
   ///
   #[default]
   Compact,
+
+  /// Input pin is at the bottom and output pin is at the top.
+  ///
+  /// +--------Out----------+
+  /// |       Header        |
+  /// +----+-----------+----+
+  /// |                     |
+  /// |        Body         |
+  /// |                     |
+  /// +---------In----------+
+  ///
+  BottomToTop,
+
+  /// Input pin is on the left and output pin is on the right.
+  ///
+  /// +---------------------+
+  /// |       Header        |
+  /// In---+-----------+--Out
+  /// |                     |
+  /// |        Body         |
+  /// +---------------------+
+  ///
+  LeftToRight,
+
+  /// Input pin is on the right and output pin is on the left.
+  ///
+  /// +---------------------+
+  /// |       Header        |
+  /// Out--+-----------+---In
+  /// |                     |
+  /// |        Body         |
+  /// +---------------------+
+  ///
+  RightToLeft,
+}
+
+impl NodeLayoutKind {
+  /// Returns sides of the node frame where input and output pins are placed.
+  const fn pin_sides(self) -> (PinSide, PinSide) {
+    match self {
+      NodeLayoutKind::Compact => (PinSide::Top, PinSide::Bottom),
+      NodeLayoutKind::BottomToTop => (PinSide::Bottom, PinSide::Top),
+      NodeLayoutKind::LeftToRight => (PinSide::Left, PinSide::Right),
+      NodeLayoutKind::RightToLeft => (PinSide::Right, PinSide::Left),
+    }
+  }
+
+  /// Returns direction in which wires leave output pins.
+  const fn wire_flow(self) -> WireFlow {
+    match self {
+      NodeLayoutKind::Compact => WireFlow::Down,
+      NodeLayoutKind::BottomToTop => WireFlow::Up,
+      NodeLayoutKind::LeftToRight => WireFlow::Right,
+      NodeLayoutKind::RightToLeft => WireFlow::Left,
+    }
+  }
+}
+
+/// Side of the node frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PinSide {
+  Top,
+  Bottom,
+  Left,
+  Right,
+}
+
+/// Line where pins of one side of the node are placed.
+#[derive(Clone, Copy, Debug)]
+enum PinLine {
+  /// Pins are centered horizontally at least between `min_x0` and `min_x1`.
+  Horizontal { y: f32, min_x0: f32, min_x1: f32 },
+
+  /// Pins are placed at the exact point.
+  Vertical { x: f32, y: f32 },
+}
+
+impl PinLine {
+  fn new(
+    side: PinSide,
+    placement: PinPlacement,
+    pin_size: f32,
+    frame_rect: Rect,
+    inner_margin: Margin,
+    header_rect: Rect,
+  ) -> Self {
+    let offset = match placement {
+      PinPlacement::Inside => -pin_size * 0.5,
+      PinPlacement::Edge => 0.0,
+      PinPlacement::Outside { margin } => pin_size.mul_add(0.5, margin),
+    };
+    let inside = matches!(placement, PinPlacement::Inside);
+    let margin = |margin: i8| if inside { f32::from(margin) } else { 0.0 };
+
+    let horizontal =
+      |y: f32| PinLine::Horizontal { y, min_x0: header_rect.min.x, min_x1: header_rect.max.x };
+
+    match side {
+      PinSide::Top => horizontal(frame_rect.top() + margin(inner_margin.top) - offset),
+      PinSide::Bottom => horizontal(frame_rect.bottom() - margin(inner_margin.bottom) + offset),
+      PinSide::Left => PinLine::Vertical {
+        x: frame_rect.left() + margin(inner_margin.left) - offset,
+        y: frame_rect.center().y,
+      },
+      PinSide::Right => PinLine::Vertical {
+        x: frame_rect.right() - margin(inner_margin.right) + offset,
+        y: frame_rect.center().y,
+      },
+    }
+  }
+
+  fn pin_rect(self, pin: &impl TreeizePin, x0: f32, x1: f32, size: f32) -> Rect {
+    match self {
+      PinLine::Horizontal { y, min_x0, min_x1 } => {
+        pin.pin_rect(min_x0.max(x0), min_x1.max(x1), y, size)
+      }
+      PinLine::Vertical { x, y } => pin.pin_rect(x, x, y, size),
+    }
+  }
 }
 
 /// Controls how node elements are laid out.
@@ -334,6 +464,25 @@ pub struct TreeizeStyle {
     )]
   pub wire_smoothness: Option<f32>,
 
+  /// Duration in seconds of node movement when layout is applied in the widget.
+  /// Zero disables animation. Defaults to `0.3`.
+  #[cfg_attr(feature = "egui-probe", egui_probe(range = 0.0..))]
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none", default))]
+  pub layout_animation_time: Option<f32>,
+
+  /// Easing curve of node movement when layout is applied in the widget.
+  /// Defaults to [`LayoutEasing::CubicInOut`].
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none", default))]
+  pub layout_animation_easing: Option<LayoutEasing>,
+
+  /// Controls whether collapsing a node hides its whole subtree.
+  /// See [`Treeize::folded_nodes`].
+  ///
+  /// Hidden nodes move along with the collapsed node when it is dragged,
+  /// and layout applied by [`TreeizeLayoutSignal`] leaves them out.
+  #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none", default))]
+  pub fold_collapsed: Option<bool>,
+
   #[doc(hidden)]
   #[cfg_attr(feature = "egui-probe", egui_probe(skip))]
   #[cfg_attr(feature = "serde", serde(skip_serializing, default))]
@@ -459,9 +608,21 @@ impl TreeizeStyle {
     self.crisp_magnified_text.unwrap_or(false)
   }
 
+  fn get_fold_collapsed(&self) -> bool {
+    self.fold_collapsed.unwrap_or(false)
+  }
+
   fn get_wire_smoothness(&self) -> f32 {
     self.wire_smoothness.unwrap_or(1.0)
   }
+
+  fn get_layout_animation_time(&self) -> f32 {
+    self.layout_animation_time.unwrap_or(0.3)
+  }
+
+  fn get_layout_animation_easing(&self) -> LayoutEasing {
+    self.layout_animation_easing.unwrap_or_default()
+  }
 }
 
 #[cfg(feature = "serde")]
@@ -547,6 +708,9 @@ impl TreeizeStyle {
       select_style: None,
       crisp_magnified_text: None,
       wire_smoothness: None,
+      layout_animation_time: None,
+      layout_animation_easing: None,
+      fold_collapsed: None,
 
       _non_exhaustive: (),
     }
@@ -581,6 +745,7 @@ struct PinResponse {
   pos: Pos2,
   wire_color: Color32,
   wire_style: WireStyle,
+  wire_flow: WireFlow,
 }
 
 /// Widget to display [`Treeize`] graph in [`Ui`].
@@ -591,14 +756,65 @@ pub struct TreeizeWidget {
   style: TreeizeStyle,
   min_size: Vec2,
   max_size: Vec2,
+  auto_layout: Option<AutoLayout>,
 }
 
 /// Signal to layout the treeize.
+///
+/// With [`LayoutAlgorithm::Force`] and no custom `layout`, the widget starts
+/// a [`ForceSimulation`] and steps it every frame until nodes settle.
+/// Nodes being dragged stay under the pointer while others move around them.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::layout::{LayoutConfig, LayoutOrientation};
+/// # use egui_treeize::ui::TreeizeLayoutSignal;
+/// let config = LayoutConfig { orientation: LayoutOrientation::LeftToRight, ..Default::default() };
+/// let signal = TreeizeLayoutSignal::new(config);
+/// assert!(signal.layout_signal && signal.layout.is_none());
+///
+/// let mut idle = TreeizeLayoutSignal::default();
+/// idle.layout_config = config;
+/// assert!(!idle.layout_signal);
+/// ```
+#[non_exhaustive]
 pub struct TreeizeLayoutSignal {
   /// Signal to layout the treeize.
   pub layout_signal: bool,
   /// Configuration for the layout.
   pub layout_config: LayoutConfig,
+  /// Custom layout algorithm used in place of the one chosen by `layout_config`.
+  ///
+  /// The config still sets folding, default node sizes and constraints,
+  /// see [`layout_tree_with`](crate::layout::layout_tree_with).
+  pub layout: Option<Box<dyn TreeLayout>>,
+}
+
+impl Default for TreeizeLayoutSignal {
+  /// Returns signal that does not lay the treeize out, with default config.
+  fn default() -> Self {
+    TreeizeLayoutSignal {
+      layout_signal: false,
+      layout_config: LayoutConfig::default(),
+      layout: None,
+    }
+  }
+}
+
+impl TreeizeLayoutSignal {
+  /// Returns signal to lay the treeize out with `layout_config`.
+  #[must_use]
+  pub fn new(layout_config: LayoutConfig) -> Self {
+    TreeizeLayoutSignal { layout_signal: true, layout_config, layout: None }
+  }
+
+  /// Uses custom layout algorithm in place of the one chosen by the config.
+  #[must_use]
+  pub fn with_layout(mut self, layout: impl TreeLayout + 'static) -> Self {
+    self.layout = Some(Box::new(layout));
+    self
+  }
 }
 
 impl Default for TreeizeWidget {
@@ -619,6 +835,7 @@ impl TreeizeWidget {
       style: TreeizeStyle::new(),
       min_size: Vec2::ZERO,
       max_size: Vec2::INFINITY,
+      auto_layout: None,
     }
   }
 
@@ -671,6 +888,17 @@ impl TreeizeWidget {
     self
   }
 
+  /// Lay the [`Treeize`] out automatically whenever the graph changes.
+  ///
+  /// See [`AutoLayout`] for what counts as a change.
+  /// [`TreeizeLayoutSignal`] still lays the graph out on demand.
+  #[inline]
+  #[must_use]
+  pub const fn auto_layout(mut self, auto_layout: AutoLayout) -> Self {
+    self.auto_layout = Some(auto_layout);
+    self
+  }
+
   #[inline]
   fn get_id(&self, ui_id: Id) -> Id {
     self.id.unwrap_or_else(|| ui_id.with(self.id_salt))
@@ -701,6 +929,7 @@ impl TreeizeWidget {
       ui,
       center_signal,
       layout_signal,
+      self.auto_layout.as_ref(),
     )
   }
 }
@@ -717,6 +946,7 @@ fn show_treeize<T, V>(
   ui: &mut Ui,
   center_signal: Option<bool>,
   layout_signal: Option<&TreeizeLayoutSignal>,
+  auto_layout: Option<&AutoLayout>,
 ) -> egui::Response
 where
   V: TreeizeViewer<T>,
@@ -854,18 +1084,37 @@ where
   let draw_order = treeize_state.update_draw_order(treeize);
   let mut drag_released = false;
 
+  let now = ui.input(|i| i.time);
+  let animation_time = style.get_layout_animation_time();
+  let animation_easing = style.get_layout_animation_easing();
+  let mut animations = NodeAnimations::load(ui.ctx(), treeize_id);
+
+  let folded = if style.get_fold_collapsed() {
+    treeize.folded_nodes()
+  } else {
+    egui::ahash::HashMap::default()
+  };
+
   let mut node_rects = Vec::new();
+  // Rects of drawn nodes, wires are routed around them.
+  let mut obstacles = Vec::new();
+  // Sizes of drawn nodes, auto-layout runs when they change.
+  let mut node_sizes = Vec::new();
 
   for node_idx in draw_order {
-    if !treeize.nodes.contains(node_idx.0) {
+    if !treeize.nodes.contains(node_idx.0) || folded.contains_key(&node_idx) {
       continue;
     }
 
     // show_node(node_idx);
+    let pos = treeize.nodes[node_idx.0].pos;
+    let pos = animations.pos(node_idx, pos, now, animation_time, animation_easing);
+
     let response = draw_node(
       treeize,
       &mut ui,
       node_idx,
+      pos,
       viewer,
       &mut treeize_state,
       &style,
@@ -886,6 +1135,10 @@ where
         pin_hovered = Some(v);
       }
       drag_released |= response.drag_released;
+      obstacles.push(response.final_rect);
+      if auto_layout.is_some() {
+        node_sizes.push((node_idx, response.final_rect.size()));
+      }
 
       if rect_selection_ended.is_some() {
         node_rects.push((node_idx, response.final_rect));
@@ -895,6 +1148,7 @@ where
 
   let mut hovered_wire = None;
   let mut wire_shapes = Vec::new();
+  let back_wires = load_back_wires(ui.ctx(), treeize_id);
 
   // Draw and interact with wires
   for wire in treeize.wires.iter() {
@@ -919,9 +1173,11 @@ where
           style.get_downscale_wire_frame(),
           from_r.pos,
           to_r.pos,
+          &obstacles,
           latest_pos,
           wire_width.max(2.0),
           pick_wire_style(from_r.wire_style, to_r.wire_style),
+          from_r.wire_flow,
         );
 
         if wire_hit {
@@ -937,6 +1193,7 @@ where
       draw_width *= 1.5;
     }
 
+    let first_shape = wire_shapes.len();
     draw_wire(
       &ui,
       WireId::Connected { treeize_id, out_pin: wire.out_pin, in_pin: wire.in_pin },
@@ -946,10 +1203,17 @@ where
       style.get_downscale_wire_frame(),
       from_r.pos,
       to_r.pos,
+      &obstacles,
       Stroke::new(draw_width, color),
       wire_threshold,
       pick_wire_style(from_r.wire_style, to_r.wire_style),
+      from_r.wire_flow,
     );
+
+    // Wires closing cycles go against the layout, draw them dashed.
+    if back_wires.contains(&(wire.out_pin, wire.in_pin)) {
+      dash_wire_shapes(&mut wire_shapes, first_shape);
+    }
   }
 
   if let Some(select_rect) = rect_selection_ended {
@@ -1083,9 +1347,11 @@ where
           style.get_downscale_wire_frame(),
           from_pos,
           to_r.pos,
+          &obstacles,
           Stroke::new(wire_width, to_r.wire_color),
           wire_threshold,
           to_r.wire_style,
+          to_r.wire_flow,
         );
       }
     }
@@ -1103,9 +1369,11 @@ where
           style.get_downscale_wire_frame(),
           from_r.pos,
           to_pos,
+          &obstacles,
           Stroke::new(wire_width, from_r.wire_color),
           wire_threshold,
           from_r.wire_style,
+          from_r.wire_flow,
         );
       }
     }
@@ -1128,25 +1396,83 @@ where
     treeize_state.node_to_top(node);
   }
 
+  // Nodes dragged by the user, force simulation leaves them in place.
+  let mut held = HashSet::new();
+
   if let Some((node, delta)) = node_moved
     && treeize.nodes.contains(node.0)
   {
     ui.ctx().request_repaint();
-    if treeize_state.selected_nodes().contains(&node) {
-      for node in treeize_state.selected_nodes() {
-        let node = &mut treeize.nodes[node.0];
-        node.pos += delta;
-      }
+    let moved = if treeize_state.selected_nodes().contains(&node) {
+      treeize_state.selected_nodes().iter().copied().collect::<HashSet<_>>()
     } else {
-      let node = &mut treeize.nodes[node.0];
-      node.pos += delta;
+      std::iter::once(node).collect()
+    };
+
+    // Hidden subtrees follow their collapsed nodes.
+    let carried = folded
+      .iter()
+      .filter(|(node, owner)| moved.contains(owner) && !moved.contains(node))
+      .map(|(&node, _)| node);
+
+    for node in moved.iter().copied().chain(carried) {
+      treeize.nodes[node.0].pos += delta;
     }
+
+    held = moved;
   }
 
+  let simulation_id = treeize_id.with("treeize-force-simulation");
+
+  // Auto-layout waits until the graph stops changing.
+  let auto_layout_due = auto_layout.is_some_and(|auto_layout| {
+    let auto_layout_id = treeize_id.with("treeize-auto-layout");
+    let state = AutoLayoutState::load(ui.ctx(), auto_layout_id);
+    let fingerprint = fingerprint(treeize, &node_sizes);
+    let (state, run) =
+      AutoLayoutState::update(state, fingerprint, !held.is_empty(), auto_layout, ui.ctx(), now);
+    state.store(ui.ctx(), auto_layout_id);
+    run
+  });
+
   if let Some(layout_signal) = layout_signal
     && layout_signal.layout_signal
   {
-    layout_with_viewer(treeize, viewer, layout_signal.layout_config, ui.ctx(), treeize_id);
+    start_layout(
+      treeize,
+      viewer,
+      &ui,
+      treeize_id,
+      layout_signal.layout.as_deref(),
+      layout_signal.layout_config,
+      &style,
+      &mut animations,
+      now,
+    );
+  } else if auto_layout_due && let Some(auto_layout) = auto_layout {
+    start_layout(
+      treeize,
+      viewer,
+      &ui,
+      treeize_id,
+      None,
+      auto_layout.config,
+      &style,
+      &mut animations,
+      now,
+    );
+  }
+
+  if let Some(mut simulation) = ui.ctx().data(|d| d.get_temp::<ForceSimulation>(simulation_id)) {
+    let result = simulation.step_with_viewer(treeize, viewer, &held, ui.ctx(), treeize_id);
+    set_back_wires(ui.ctx(), treeize_id, result.back_edges);
+
+    if simulation.is_settled() {
+      ui.ctx().data_mut(|d| d.remove::<ForceSimulation>(simulation_id));
+    } else {
+      ui.ctx().data_mut(|d| d.insert_temp(simulation_id, simulation));
+      ui.ctx().request_repaint();
+    }
   }
 
   // Do centering unless no nodes are present.
@@ -1155,7 +1481,7 @@ where
   {
     let mut nodes_bb = Rect::NOTHING;
 
-    for (node_id, _) in treeize.node_ids() {
+    for (node_id, _) in treeize.node_ids().filter(|(node_id, _)| !folded.contains_key(node_id)) {
       let node_state_id = treeize_id.with(("treeize-node", node_id));
       if let Some(node_data) = NodeState::pick_data(ui.ctx(), node_state_id) {
         nodes_bb =
@@ -1173,11 +1499,62 @@ where
     treeize_state.deselect_all_nodes();
   }
 
+  animations.store(ui.ctx(), treeize_id, now, animation_time);
   treeize_state.store(treeize, ui.ctx());
 
   treeize_resp
 }
 
+/// Lays the treeize out with `layout` or the algorithm of `layout_config`,
+/// animating nodes to their new positions.
+///
+/// With [`LayoutAlgorithm::Force`] and no custom `layout` a [`ForceSimulation`] is started instead,
+/// it is stepped in the following frames.
+#[allow(clippy::too_many_arguments)]
+fn start_layout<T, V>(
+  treeize: &mut Treeize<T>,
+  viewer: &mut V,
+  ui: &Ui,
+  treeize_id: Id,
+  layout: Option<&dyn TreeLayout>,
+  mut layout_config: LayoutConfig,
+  style: &TreeizeStyle,
+  animations: &mut NodeAnimations,
+  now: f64,
+) where
+  V: TreeizeViewer<T>,
+{
+  let simulation_id = treeize_id.with("treeize-force-simulation");
+  layout_config.fold_collapsed |= style.get_fold_collapsed();
+
+  if layout.is_none() && layout_config.algorithm == LayoutAlgorithm::Force {
+    // Nodes settle over the following frames.
+    let simulation = ForceSimulation::new(layout_config);
+    ui.ctx().data_mut(|d| d.insert_temp(simulation_id, simulation));
+    return;
+  }
+  ui.ctx().data_mut(|d| d.remove::<ForceSimulation>(simulation_id));
+
+  let old_positions =
+    treeize.nodes.iter().map(|(idx, node)| (NodeId(idx), node.pos)).collect::<Vec<_>>();
+
+  let layout = layout.unwrap_or(&layout_config);
+  let result =
+    layout_with_viewer_using(treeize, viewer, layout, layout_config, ui.ctx(), treeize_id);
+  set_back_wires(ui.ctx(), treeize_id, result.back_edges);
+
+  // Nodes are already at their new positions, but are drawn moving there.
+  let moves = old_positions
+    .into_iter()
+    .filter_map(|(node, from)| result.positions.get(&node).map(|&to| (node, from, to)));
+  animations.start(
+    moves,
+    now,
+    style.get_layout_animation_time(),
+    style.get_layout_animation_easing(),
+  );
+}
+
 #[allow(clippy::too_many_arguments)]
 #[allow(clippy::too_many_lines)]
 fn draw_inputs<T, V>(
@@ -1190,9 +1567,8 @@ fn draw_inputs<T, V>(
   node_ui: &mut Ui,
   inputs_rect: Rect,
   payload_clip_rect: Rect,
-  input_y: f32,
-  min_pin_x_left: f32,
-  min_pin_x_right: f32,
+  pin_line: PinLine,
+  wire_flow: WireFlow,
   treeize_state: &mut TreeizeState,
   modifiers: Modifiers,
   input_positions: &mut HashMap<InPinId, PinResponse>,
@@ -1239,8 +1615,7 @@ where
         return;
       }
 
-      let pin_rect =
-        treeize_pin.pin_rect(min_pin_x_left.max(x0), min_pin_x_right.max(x1), input_y, pin_size);
+      let pin_rect = pin_line.pin_rect(&treeize_pin, x0, x1, pin_size);
 
       // Interact with pin shape.
       pin_ui.set_clip_rect(treeize_clip_rect);
@@ -1302,6 +1677,7 @@ where
           pos: r.rect.center(),
           wire_color: wire_info.color,
           wire_style: wire_info.style,
+          wire_flow,
         },
       );
 
@@ -1327,9 +1703,8 @@ fn draw_outputs<T, V>(
   node_ui: &mut Ui,
   outputs_rect: Rect,
   payload_clip_rect: Rect,
-  output_y: f32,
-  min_pin_y_left: f32,
-  min_pin_y_right: f32,
+  pin_line: PinLine,
+  wire_flow: WireFlow,
   treeize_state: &mut TreeizeState,
   modifiers: Modifiers,
   output_positions: &mut HashMap<OutPinId, PinResponse>,
@@ -1376,8 +1751,7 @@ where
         return;
       }
 
-      let pin_rect =
-        treeize_pin.pin_rect(min_pin_y_left.max(x0), min_pin_y_right.max(x1), output_y, pin_size);
+      let pin_rect = pin_line.pin_rect(&treeize_pin, x0, x1, pin_size);
 
       pin_ui.set_clip_rect(treeize_clip_rect);
 
@@ -1439,6 +1813,7 @@ where
           pos: r.rect.center(),
           wire_color: wire_info.color,
           wire_style: wire_info.style,
+          wire_flow,
         },
       );
 
@@ -1491,6 +1866,7 @@ fn draw_node<T, V>(
   treeize: &mut Treeize<T>,
   ui: &mut Ui,
   node: NodeId,
+  pos: Pos2,
   viewer: &mut V,
   treeize_state: &mut TreeizeState,
   style: &TreeizeStyle,
@@ -1502,7 +1878,7 @@ fn draw_node<T, V>(
 where
   V: TreeizeViewer<T>,
 {
-  let Node { pos, open, ref value } = treeize.nodes[node.0];
+  let Node { open, ref value, .. } = treeize.nodes[node.0];
 
   let inputs_count = usize::from(viewer.has_input(value));
   let outputs_count = usize::from(viewer.has_output(value));
@@ -1617,24 +1993,6 @@ where
       viewer.apply_node_style(ui.style_mut(), node, &inputs, &outputs, treeize);
     }
 
-    // Input pins' center side by Y axis.
-    let input_y = match pin_placement {
-      PinPlacement::Inside => {
-        pin_size.mul_add(0.5, node_frame_rect.top() + node_frame.inner_margin.topf())
-      }
-      PinPlacement::Edge => node_frame_rect.top(),
-      PinPlacement::Outside { margin } => pin_size.mul_add(-0.5, node_frame_rect.top() - margin),
-    };
-
-    // Output pins' center side by Y axis.
-    let output_y = match pin_placement {
-      PinPlacement::Inside => {
-        pin_size.mul_add(-0.5, node_frame_rect.bottom() - node_frame.inner_margin.bottomf())
-      }
-      PinPlacement::Edge => node_frame_rect.bottom(),
-      PinPlacement::Outside { margin } => pin_size.mul_add(0.5, node_frame_rect.bottom() + margin),
-    };
-
     // Input/output pin block
 
     if (openness < 1.0 && open) || (openness > 0.0 && !open) {
@@ -1733,8 +2091,25 @@ where
     let header_size = header_rect.size();
     node_state.set_header_height(header_size.y);
 
+    let (input_side, output_side) = node_layout.kind.pin_sides();
+    let wire_flow = node_layout.kind.wire_flow();
+
+    let pin_line = |side| {
+      PinLine::new(
+        side,
+        pin_placement,
+        pin_size,
+        node_frame_rect,
+        node_frame.inner_margin,
+        header_rect,
+      )
+    };
+
     match node_layout.kind {
-      NodeLayoutKind::Compact => {
+      NodeLayoutKind::Compact
+      | NodeLayoutKind::BottomToTop
+      | NodeLayoutKind::LeftToRight
+      | NodeLayoutKind::RightToLeft => {
         let r = draw_inputs(
           treeize,
           viewer,
@@ -1745,9 +2120,8 @@ where
           ui,
           payload_rect,
           payload_clip_rect,
-          input_y,
-          header_rect.min.x,
-          header_rect.max.x,
+          pin_line(input_side),
+          wire_flow,
           treeize_state,
           modifiers,
           input_positions,
@@ -1768,9 +2142,8 @@ where
           ui,
           payload_rect,
           payload_clip_rect,
-          output_y,
-          header_rect.min.x,
-          header_rect.max.x,
+          pin_line(output_side),
+          wire_flow,
           treeize_state,
           modifiers,
           output_positions,
@@ -1839,6 +2212,7 @@ impl<T> Treeize<T> {
       ui,
       None,
       None,
+      None,
     );
   }
 }
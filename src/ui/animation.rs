@@ -0,0 +1,145 @@
+use egui::{Context, Id, Pos2, ahash::HashMap, emath::easing};
+
+use crate::NodeId;
+
+/// Easing curve of node movement when layout is applied in the widget.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "egui-probe", derive(egui_probe::EguiProbe))]
+pub enum LayoutEasing {
+  /// Constant speed.
+  Linear,
+
+  /// Starts fast and slows down.
+  QuadraticOut,
+
+  /// Starts slow, speeds up and slows down again.
+  QuadraticInOut,
+
+  /// Starts fast and slows down, stronger than [`LayoutEasing::QuadraticOut`].
+  CubicOut,
+
+  /// Starts slow, speeds up and slows down again,
+  /// stronger than [`LayoutEasing::QuadraticInOut`].
+  #[default]
+  CubicInOut,
+
+  /// Follows a sine wave, starts and ends slow.
+  SinInOut,
+
+  /// Overshoots the target a little and comes back.
+  BackOut,
+}
+
+impl LayoutEasing {
+  /// Maps linear progress in `0..=1` to eased progress.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use egui_treeize::ui::LayoutEasing;
+  /// assert_eq!(LayoutEasing::Linear.apply(0.25), 0.25);
+  /// assert_eq!(LayoutEasing::CubicInOut.apply(1.0), 1.0);
+  /// assert!(LayoutEasing::CubicOut.apply(0.25) > 0.25);
+  /// ```
+  #[must_use]
+  pub fn apply(self, t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    match self {
+      LayoutEasing::Linear => easing::linear(t),
+      LayoutEasing::QuadraticOut => easing::quadratic_out(t),
+      LayoutEasing::QuadraticInOut => easing::quadratic_in_out(t),
+      LayoutEasing::CubicOut => easing::cubic_out(t),
+      LayoutEasing::CubicInOut => easing::cubic_in_out(t),
+      LayoutEasing::SinInOut => easing::sin_in_out(t),
+      LayoutEasing::BackOut => easing::back_out(t),
+    }
+  }
+}
+
+/// Movement of a node from where it was drawn to its new position.
+#[derive(Clone, Copy)]
+struct NodeAnimation {
+  from: Pos2,
+  to: Pos2,
+  start: f64,
+}
+
+/// Nodes of the widget that are moving to positions set by layout.
+///
+/// Positions in the treeize are already the targets,
+/// animations only change where nodes are drawn.
+#[derive(Clone, Default)]
+pub(crate) struct NodeAnimations {
+  nodes: HashMap<NodeId, NodeAnimation>,
+}
+
+impl NodeAnimations {
+  pub fn load(cx: &Context, id: Id) -> Self {
+    cx.data(|d| d.get_temp::<Self>(id)).unwrap_or_default()
+  }
+
+  /// Saves animations that are not finished yet and requests repaint for them.
+  pub fn store(mut self, cx: &Context, id: Id, now: f64, duration: f32) {
+    self.nodes.retain(|_, animation| now < animation.start + f64::from(duration));
+
+    if self.nodes.is_empty() {
+      cx.data_mut(|d| d.remove_temp::<Self>(id));
+    } else {
+      cx.data_mut(|d| d.insert_temp::<Self>(id, self));
+      cx.request_repaint();
+    }
+  }
+
+  /// Returns where the node at `pos` is drawn now.
+  ///
+  /// A node that was moved elsewhere since the animation started, e.g. dragged, stops animating.
+  pub fn pos(
+    &self,
+    node: NodeId,
+    pos: Pos2,
+    now: f64,
+    duration: f32,
+    easing: LayoutEasing,
+  ) -> Pos2 {
+    let Some(animation) = self.nodes.get(&node) else {
+      return pos;
+    };
+
+    if animation.to != pos || duration <= 0.0 {
+      return pos;
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    let t = ((now - animation.start) / f64::from(duration)) as f32;
+    if t >= 1.0 {
+      return pos;
+    }
+
+    animation.from.lerp(animation.to, easing.apply(t))
+  }
+
+  /// Starts moving nodes from their drawn positions to new ones.
+  pub fn start(
+    &mut self,
+    moves: impl IntoIterator<Item = (NodeId, Pos2, Pos2)>,
+    now: f64,
+    duration: f32,
+    easing: LayoutEasing,
+  ) {
+    if duration <= 0.0 {
+      return;
+    }
+
+    for (node, from, to) in moves {
+      if from == to {
+        continue;
+      }
+
+      // Node that is still moving starts from where it is drawn now.
+      let from = self.pos(node, from, now, duration, easing);
+
+      self.nodes.insert(node, NodeAnimation { from, to, start: now });
+    }
+  }
+}
@@ -0,0 +1,129 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use egui::{Context, Id, Vec2};
+
+use crate::{NodeId, Treeize, layout::LayoutConfig};
+
+/// Options of the auto-layout mode of [`TreeizeWidget`](super::TreeizeWidget).
+///
+/// The widget lays the graph out again when nodes or wires are added or removed,
+/// when nodes change size or when they are collapsed or expanded.
+/// Changes that come in quick succession are laid out once,
+/// after nothing changed for [`AutoLayout::debounce`] seconds.
+///
+/// # Examples
+///
+/// ```
+/// # use egui_treeize::ui::{AutoLayout, TreeizeWidget};
+/// let widget = TreeizeWidget::new().auto_layout(AutoLayout { snap_back: true, ..Default::default() });
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoLayout {
+  /// Configuration for the layout.
+  pub config: LayoutConfig,
+
+  /// Time in seconds the graph must stay unchanged before it is laid out.
+  pub debounce: f32,
+
+  /// If true, nodes dragged by the user return to their place in the layout
+  /// when they are released.
+  /// Otherwise they stay where they were dropped until the graph changes.
+  pub snap_back: bool,
+}
+
+impl Default for AutoLayout {
+  fn default() -> Self {
+    AutoLayout { config: LayoutConfig::default(), debounce: 0.25, snap_back: false }
+  }
+}
+
+/// Changes of the graph seen by the auto-layout mode.
+#[derive(Clone, Copy)]
+pub(crate) struct AutoLayoutState {
+  fingerprint: u64,
+  /// Time of the last change that was not laid out yet.
+  changed_at: Option<f64>,
+  dragging: bool,
+}
+
+impl AutoLayoutState {
+  pub fn load(cx: &Context, id: Id) -> Option<Self> {
+    cx.data(|d| d.get_temp::<Self>(id))
+  }
+
+  pub fn store(self, cx: &Context, id: Id) {
+    cx.data_mut(|d| d.insert_temp(id, self));
+  }
+
+  /// Records the graph of this frame and returns true if it is time to lay it out.
+  ///
+  /// Nothing is laid out while nodes are dragged.
+  /// Requests repaint when a change waits for the debounce time to pass.
+  pub fn update(
+    state: Option<Self>,
+    fingerprint: u64,
+    dragging: bool,
+    options: &AutoLayout,
+    cx: &Context,
+    now: f64,
+  ) -> (Self, bool) {
+    let mut state =
+      state.unwrap_or(AutoLayoutState { fingerprint, changed_at: Some(now), dragging: false });
+
+    if state.fingerprint != fingerprint {
+      state.fingerprint = fingerprint;
+      state.changed_at = Some(now);
+    }
+
+    if options.snap_back && state.dragging && !dragging {
+      // Dropped nodes go back without waiting.
+      state.changed_at = Some(f64::NEG_INFINITY);
+    }
+    state.dragging = dragging;
+
+    let Some(changed_at) = state.changed_at else {
+      return (state, false);
+    };
+    if dragging {
+      return (state, false);
+    }
+
+    let wait = changed_at + f64::from(options.debounce) - now;
+    if wait > 0.0 {
+      #[allow(clippy::cast_possible_truncation)]
+      cx.request_repaint_after_secs(wait as f32);
+      return (state, false);
+    }
+
+    state.changed_at = None;
+    (state, true)
+  }
+}
+
+/// Returns hash of everything that makes the auto-layout mode lay the graph out again:
+/// nodes, wires, whether nodes are open and `sizes` of drawn nodes.
+pub(crate) fn fingerprint<T>(treeize: &Treeize<T>, sizes: &[(NodeId, Vec2)]) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  for (idx, node) in &treeize.nodes {
+    (idx, node.open).hash(&mut hasher);
+  }
+
+  // Wires and sizes come in no particular order, their hashes are summed up.
+  let wires = treeize
+    .wires
+    .iter()
+    .fold(0u64, |sum, wire| sum.wrapping_add(hash_one((wire.out_pin, wire.in_pin))));
+  let sizes = sizes.iter().fold(0u64, |sum, (node, size)| {
+    let size = size.round();
+    sum.wrapping_add(hash_one((node, size.x.to_bits(), size.y.to_bits())))
+  });
+
+  (wires, sizes).hash(&mut hasher);
+  hasher.finish()
+}
+
+fn hash_one(value: impl Hash) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  value.hash(&mut hasher);
+  hasher.finish()
+}
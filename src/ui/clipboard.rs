@@ -0,0 +1,107 @@
+use egui::{Event, Response, Ui};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{InPinId, NodeId, OutPinId, Treeize, clipboard::TreeizeFragment};
+
+use super::{
+  TreeizeViewer, TreeizeWidget,
+  state::{get_selected_nodes, load_to_global, set_selected_nodes},
+};
+
+impl TreeizeWidget {
+  /// Handles copy, cut and paste of the selected nodes.
+  ///
+  /// Call it after [`TreeizeWidget::show`] with the same `Ui` and the response returned by it.
+  /// Events are handled only while the pointer is over the widget and no other widget has focus.
+  ///
+  /// Copy and cut put selected nodes and wires among them to the system clipboard.
+  /// Paste recreates nodes from the clipboard at the pointer position and selects them.
+  /// If `attach_to_selected` is true, pasted subtrees are connected under the last selected node.
+  /// Subtrees are attached only if `viewer` reports an output pin for the selected node
+  /// and an input pin for the pasted root.
+  ///
+  /// Returns ids of pasted nodes.
+  pub fn handle_clipboard<T, V>(
+    &self,
+    treeize: &mut Treeize<T>,
+    viewer: &mut V,
+    response: &Response,
+    ui: &Ui,
+    attach_to_selected: bool,
+  ) -> Vec<NodeId>
+  where
+    T: Clone + Serialize + DeserializeOwned,
+    V: TreeizeViewer<T>,
+  {
+    let mut pasted = Vec::new();
+
+    if !response.contains_pointer() || ui.memory(|m| m.focused().is_some()) {
+      return pasted;
+    }
+
+    let treeize_id = self.get_id(ui.id());
+    let events = ui.input(|i| i.events.clone());
+
+    for event in events {
+      match event {
+        Event::Copy | Event::Cut => {
+          let selected = get_selected_nodes(treeize_id, ui.ctx());
+          if selected.is_empty() {
+            continue;
+          }
+
+          let fragment = treeize.copy_nodes(&selected);
+          if let Ok(text) = fragment.to_clipboard_text() {
+            ui.ctx().copy_text(text);
+          }
+
+          if matches!(event, Event::Cut) {
+            for node in selected {
+              if treeize.nodes.contains(node.0) {
+                treeize.remove_node(node);
+              }
+            }
+          }
+        }
+        Event::Paste(text) => {
+          let Ok(fragment) = TreeizeFragment::<T>::from_clipboard_text(&text) else {
+            continue;
+          };
+
+          let Some(to_global) = load_to_global(ui.ctx(), treeize_id) else {
+            continue;
+          };
+
+          let pointer = ui.input(|i| i.pointer.latest_pos()).unwrap_or(response.rect.center());
+          let pos = to_global.inverse() * pointer;
+
+          let roots = fragment.roots();
+          let ids = treeize.paste_fragment(fragment, pos);
+
+          if attach_to_selected
+            && let Some(&parent) = get_selected_nodes(treeize_id, ui.ctx()).last()
+            && treeize.nodes.contains(parent.0)
+            && viewer.has_output(&treeize[parent])
+          {
+            for root in roots {
+              if viewer.has_input(&treeize[ids[root]]) {
+                treeize.connect(
+                  OutPinId { node: parent, output: 0 },
+                  InPinId { node: ids[root], input: 0 },
+                );
+              }
+            }
+          }
+
+          set_selected_nodes(ui.ctx(), treeize_id, &ids);
+          ui.ctx().request_repaint();
+
+          pasted.extend(ids);
+        }
+        _ => {}
+      }
+    }
+
+    pasted
+  }
+}
@@ -224,6 +224,52 @@ impl TreeizeStateData {
   }
 }
 
+/// Wires that closed cycles in the last layout of the widget.
+#[derive(Clone, Default)]
+struct BackWires(HashSet<(OutPinId, InPinId)>);
+
+impl BackWires {
+  fn save(self, cx: &Context, id: Id) {
+    cx.data_mut(|d| {
+      if self.0.is_empty() {
+        d.remove_temp::<Self>(id);
+      } else {
+        d.insert_temp::<Self>(id, self);
+      }
+    });
+  }
+
+  fn load(cx: &Context, id: Id) -> Self {
+    cx.data(|d| d.get_temp::<Self>(id)).unwrap_or_default()
+  }
+}
+
+/// Replaces wires that are drawn as back edges of the layout.
+pub(crate) fn set_back_wires(
+  cx: &Context,
+  id: Id,
+  wires: impl IntoIterator<Item = (OutPinId, InPinId)>,
+) {
+  BackWires(wires.into_iter().collect()).save(cx, id);
+}
+
+/// Returns wires that are drawn as back edges of the layout.
+pub(crate) fn load_back_wires(cx: &Context, id: Id) -> HashSet<(OutPinId, InPinId)> {
+  BackWires::load(cx, id).0
+}
+
+/// Returns viewport transform of the widget if it was shown.
+#[cfg(feature = "serde")]
+pub(crate) fn load_to_global(cx: &Context, id: Id) -> Option<TSTransform> {
+  TreeizeStateData::load(cx, id).map(|data| data.to_global)
+}
+
+/// Replaces selected nodes of the widget.
+#[cfg(feature = "serde")]
+pub(crate) fn set_selected_nodes(cx: &Context, id: Id, nodes: &[NodeId]) {
+  SelectedNodes(nodes.to_smallvec()).save(cx, id);
+}
+
 fn prune_selected_nodes<T>(
   selected_nodes: &mut SmallVec<[NodeId; 8]>,
   treeize: &Treeize<T>,
@@ -567,7 +613,97 @@ impl TreeizeState {
   }
 }
 
+/// Snapshot of the view state of a [`TreeizeWidget`].
+///
+/// Contains state that is kept by the widget in egui memory and is not part of the [`Treeize`]:
+/// viewport pan and zoom, selection and draw order of nodes.
+/// egui keeps this state only for the current session, use [`TreeizeWidget::save_view_state`]
+/// and [`TreeizeWidget::load_view_state`] to persist it across restarts.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TreeizeViewState {
+  /// Transform from graph space to screen space.
+  /// Encodes viewport pan and zoom.
+  pub to_global: TSTransform,
+
+  /// List of selected nodes.
+  /// Last selected node is the last in the list.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub selected_nodes: Vec<NodeId>,
+
+  /// Order in which nodes are drawn.
+  /// Last node is drawn on top.
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub draw_order: Vec<NodeId>,
+}
+
 impl TreeizeWidget {
+  /// Returns snapshot of the view state for the `TreeizeWidget` with same id.
+  ///
+  /// Returns `None` if the widget was not shown yet.
+  ///
+  /// Use same `Ui` instance that was used in [`TreeizeWidget::show`].
+  #[must_use]
+  #[inline]
+  pub fn save_view_state(self, ui: &Ui) -> Option<TreeizeViewState> {
+    self.save_view_state_at(ui.id(), ui.ctx())
+  }
+
+  /// Returns snapshot of the view state for the `TreeizeWidget` with same id.
+  ///
+  /// Returns `None` if the widget was not shown yet.
+  ///
+  /// `ui_id` must be the Id of the `Ui` instance that was used in [`TreeizeWidget::show`].
+  #[must_use]
+  pub fn save_view_state_at(self, ui_id: Id, ctx: &Context) -> Option<TreeizeViewState> {
+    let treeize_id = self.get_id(ui_id);
+
+    let data = TreeizeStateData::load(ctx, treeize_id)?;
+
+    Some(TreeizeViewState {
+      to_global: data.to_global,
+      selected_nodes: SelectedNodes::load(ctx, treeize_id).0.into_vec(),
+      draw_order: DrawOrder::load(ctx, treeize_id).0,
+    })
+  }
+
+  /// Restores view state for the `TreeizeWidget` with same id.
+  ///
+  /// Call it before [`TreeizeWidget::show`], e.g. on the first frame after restart.
+  /// Nodes that no longer exist in the [`Treeize`] are ignored.
+  ///
+  /// Use same `Ui` instance that will be used in [`TreeizeWidget::show`].
+  #[inline]
+  pub fn load_view_state(self, ui: &Ui, state: &TreeizeViewState) {
+    self.load_view_state_at(ui.id(), ui.ctx(), state);
+  }
+
+  /// Restores view state for the `TreeizeWidget` with same id.
+  ///
+  /// Call it before [`TreeizeWidget::show`], e.g. on the first frame after restart.
+  /// Nodes that no longer exist in the [`Treeize`] are ignored.
+  ///
+  /// `ui_id` must be the Id of the `Ui` instance that will be used in [`TreeizeWidget::show`].
+  pub fn load_view_state_at(self, ui_id: Id, ctx: &Context, state: &TreeizeViewState) {
+    let treeize_id = self.get_id(ui_id);
+
+    let data = match TreeizeStateData::load(ctx, treeize_id) {
+      Some(data) => TreeizeStateData { to_global: state.to_global, ..data },
+      None => TreeizeStateData {
+        to_global: state.to_global,
+        new_wires: None,
+        new_wires_menu: false,
+        rect_selection: None,
+      },
+    };
+    data.save(ctx, treeize_id);
+
+    DrawOrder(state.draw_order.clone()).save(ctx, treeize_id);
+    SelectedNodes(state.selected_nodes.to_smallvec()).save(ctx, treeize_id);
+
+    ctx.request_repaint();
+  }
+
   /// Returns list of nodes selected in the UI for the `TreeizeWidget` with same id.
   ///
   /// Use same `Ui` instance that was used in [`TreeizeWidget::show`].
@@ -1,6 +1,6 @@
-use egui::{Painter, Pos2, Rect, Style, Ui, emath::TSTransform};
+use egui::{Painter, Pos2, Rect, Style, Ui, Vec2, emath::TSTransform};
 
-use crate::{InPin, InPinId, NodeId, OutPin, OutPinId, Treeize};
+use crate::{InPin, InPinId, NodeId, OutPin, OutPinId, Treeize, layout::LayoutConstraint};
 
 use super::{
   BackgroundPattern, NodeLayout, TreeizeStyle,
@@ -101,6 +101,100 @@ pub trait TreeizeViewer<T> {
     default
   }
 
+  /// Returns constraint on the node's position for layout applied with this viewer.
+  ///
+  /// Nodes without constraints are arranged around constrained ones,
+  /// see [`layout_tree_constrained`](crate::layout::layout_tree_constrained).
+  /// Return [`LayoutConstraint::Pinned`] with the node's current position
+  /// to keep a hand-placed node where it is.
+  ///
+  /// Returns `None` by default.
+  #[inline]
+  fn layout_constraint(&mut self, node: NodeId, treeize: &Treeize<T>) -> Option<LayoutConstraint> {
+    let _ = (node, treeize);
+    None
+  }
+
+  /// Returns weight of the wire for layout applied with this viewer.
+  ///
+  /// Children are placed as far from their parents as weights of the wires say,
+  /// see [`layout_tree_weighted`](crate::layout::layout_tree_weighted).
+  ///
+  /// Returns `1.0` by default, which is the usual spacing between levels.
+  #[inline]
+  fn layout_weight(&mut self, out_pin: OutPinId, in_pin: InPinId, treeize: &Treeize<T>) -> f32 {
+    let _ = (out_pin, in_pin, treeize);
+    1.0
+  }
+
+  /// Returns estimated size of the node for layout applied with this viewer.
+  ///
+  /// Sizes are measured when nodes are drawn, so they are unknown before the first frame
+  /// and in layouts computed without a widget.
+  /// Estimates are used for nodes that were not measured yet,
+  /// otherwise such nodes take [`LayoutConfig::horizontal_spacing`](crate::layout::LayoutConfig::horizontal_spacing)
+  /// by [`LayoutConfig::vertical_spacing`](crate::layout::LayoutConfig::vertical_spacing).
+  ///
+  /// Returns `None` by default.
+  ///
+  /// # Examples
+  ///
+  /// ```
+  /// # use egui::Ui;
+  /// # use egui_treeize::{InPin, InPinId, NodeId, OutPin, OutPinId, Treeize};
+  /// # use egui_treeize::layout::{LayoutConfig, layout_with_viewer};
+  /// # use egui_treeize::ui::{PinInfo, TreeizeViewer};
+  /// struct Viewer;
+  ///
+  /// impl TreeizeViewer<String> for Viewer {
+  ///   fn title(&mut self, node: &String) -> String {
+  ///     node.clone()
+  ///   }
+  ///
+  ///   fn has_input(&mut self, _: &String) -> bool {
+  ///     true
+  ///   }
+  ///
+  ///   fn has_output(&mut self, _: &String) -> bool {
+  ///     true
+  ///   }
+  ///
+  ///   fn show_input(&mut self, _: &InPin, _: &mut Ui, _: &mut Treeize<String>) -> PinInfo {
+  ///     PinInfo::default()
+  ///   }
+  ///
+  ///   fn show_output(&mut self, _: &OutPin, _: &mut Ui, _: &mut Treeize<String>) -> PinInfo {
+  ///     PinInfo::default()
+  ///   }
+  ///
+  ///   fn estimate_size(&mut self, node: NodeId, treeize: &Treeize<String>) -> Option<egui::Vec2> {
+  ///     #[allow(clippy::cast_precision_loss)]
+  ///     Some(egui::vec2(treeize[node].len() as f32 * 8.0 + 40.0, 30.0))
+  ///   }
+  /// }
+  ///
+  /// let mut treeize = Treeize::new();
+  /// let root = treeize.insert_node(egui::pos2(0.0, 0.0), "root".to_owned());
+  /// let long = treeize.insert_node(egui::pos2(0.0, 0.0), "a very long title of a node".repeat(10));
+  /// let short = treeize.insert_node(egui::pos2(0.0, 0.0), "short".to_owned());
+  /// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: long, input: 0 });
+  /// treeize.connect(OutPinId { node: root, output: 0 }, InPinId { node: short, input: 0 });
+  ///
+  /// // No frame was drawn yet, sizes come from the estimates.
+  /// let ctx = egui::Context::default();
+  /// let config = LayoutConfig::default();
+  /// let result = layout_with_viewer(&mut treeize, &mut Viewer, config, &ctx, egui::Id::new("tree"));
+  ///
+  /// let width = treeize[long].len() as f32 * 8.0 + 40.0;
+  /// let distance = (result.positions[&short].x - result.positions[&long].x).abs();
+  /// assert!(distance >= width);
+  /// ```
+  #[inline]
+  fn estimate_size(&mut self, node: NodeId, treeize: &Treeize<T>) -> Option<Vec2> {
+    let _ = (node, treeize);
+    None
+  }
+
   /// Renders elements inside the node's header frame.
   ///
   /// This is the good place to show the node's title and controls related to the whole node.
@@ -1,9 +1,14 @@
 use core::f32;
 
-use egui::{Context, Id, Pos2, Rect, Shape, Stroke, Ui, ahash::HashMap, cache::CacheTrait, pos2};
+use egui::{
+  Context, Id, Pos2, Rect, Shape, Stroke, Ui, ahash::HashMap, cache::CacheTrait, epaint::ColorMode,
+  pos2,
+};
 
 use crate::{InPinId, OutPinId};
 
+mod route;
+
 const MAX_CURVE_SAMPLES: usize = 100;
 
 /// Layer where wires are rendered.
@@ -46,6 +51,15 @@ pub enum WireStyle {
     corner_radius: f32,
   },
 
+  /// Draw wire as straight lines with 90 degree turns, routed around nodes.
+  /// Corners has radius of `corner_radius`.
+  ///
+  /// Routes are cached per wire and found again only when nodes near the wire move.
+  Routed {
+    /// Radius of corners in wire.
+    corner_radius: f32,
+  },
+
   /// Draw wire as 3rd degree Bezier curve.
   Bezier3,
 
@@ -54,6 +68,65 @@ pub enum WireStyle {
   Bezier5,
 }
 
+/// Direction in which wires leave output pins and enter input pins.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WireFlow {
+  /// Wires go down from outputs.
+  #[default]
+  Down,
+
+  /// Wires go up from outputs.
+  Up,
+
+  /// Wires go right from outputs.
+  Right,
+
+  /// Wires go left from outputs.
+  Left,
+}
+
+impl WireFlow {
+  /// Maps point into the frame where wires flow right.
+  const fn enter_horizontal(self, p: Pos2) -> Pos2 {
+    match self {
+      WireFlow::Right => p,
+      WireFlow::Left => pos2(-p.x, p.y),
+      WireFlow::Down => pos2(p.y, p.x),
+      WireFlow::Up => pos2(-p.y, p.x),
+    }
+  }
+
+  /// Inverse of [`WireFlow::enter_horizontal`].
+  const fn restore_horizontal(self, p: Pos2) -> Pos2 {
+    match self {
+      WireFlow::Right => p,
+      WireFlow::Left => pos2(-p.x, p.y),
+      WireFlow::Down => pos2(p.y, p.x),
+      WireFlow::Up => pos2(p.y, -p.x),
+    }
+  }
+
+  /// Maps point into the frame where wires flow down.
+  const fn enter_vertical(self, p: Pos2) -> Pos2 {
+    match self {
+      WireFlow::Down => p,
+      WireFlow::Up => pos2(p.x, -p.y),
+      WireFlow::Right => pos2(p.y, p.x),
+      WireFlow::Left => pos2(p.y, -p.x),
+    }
+  }
+
+  /// Inverse of [`WireFlow::enter_vertical`].
+  const fn restore_vertical(self, p: Pos2) -> Pos2 {
+    match self {
+      WireFlow::Down => p,
+      WireFlow::Up => pos2(p.x, -p.y),
+      WireFlow::Right => pos2(p.y, p.x),
+      WireFlow::Left => pos2(-p.y, p.x),
+    }
+  }
+}
+
 pub const fn pick_wire_style(left: WireStyle, right: WireStyle) -> WireStyle {
   match (left, right) {
     (WireStyle::Line, _) | (_, WireStyle::Line) => WireStyle::Line,
@@ -62,6 +135,12 @@ pub const fn pick_wire_style(left: WireStyle, right: WireStyle) -> WireStyle {
     }
     (WireStyle::AxisAligned { corner_radius }, _)
     | (_, WireStyle::AxisAligned { corner_radius }) => WireStyle::AxisAligned { corner_radius },
+    (WireStyle::Routed { corner_radius: a }, WireStyle::Routed { corner_radius: b }) => {
+      WireStyle::Routed { corner_radius: f32::max(a, b) }
+    }
+    (WireStyle::Routed { corner_radius }, _) | (_, WireStyle::Routed { corner_radius }) => {
+      WireStyle::Routed { corner_radius }
+    }
     (WireStyle::Bezier3, _) | (_, WireStyle::Bezier3) => WireStyle::Bezier3,
     (WireStyle::Bezier5, WireStyle::Bezier5) => WireStyle::Bezier5,
   }
@@ -85,7 +164,15 @@ fn adjust_frame_size(
 }
 
 /// Returns 5th degree bezier curve control points for the wire
-fn wire_bezier_5(frame_size: f32, from: Pos2, to: Pos2) -> [Pos2; 6] {
+/// bent along the direction of `flow`.
+fn wire_bezier_5(frame_size: f32, from: Pos2, to: Pos2, flow: WireFlow) -> [Pos2; 6] {
+  let points =
+    wire_bezier_5_horizontal(frame_size, flow.enter_horizontal(from), flow.enter_horizontal(to));
+  points.map(|p| flow.restore_horizontal(p))
+}
+
+/// Returns 5th degree bezier curve control points for the wire flowing right.
+fn wire_bezier_5_horizontal(frame_size: f32, from: Pos2, to: Pos2) -> [Pos2; 6] {
   let from_norm_x = frame_size;
   let from_2 = pos2(from.x + from_norm_x, from.y);
   let to_norm_x = -from_norm_x;
@@ -189,8 +276,8 @@ fn wire_bezier_5(frame_size: f32, from: Pos2, to: Pos2) -> [Pos2; 6] {
 }
 
 /// Returns 3rd degree bezier curve control points for the wire
-fn wire_bezier_3(frame_size: f32, from: Pos2, to: Pos2) -> [Pos2; 4] {
-  let [a, b, _, _, c, d] = wire_bezier_5(frame_size, from, to);
+fn wire_bezier_3(frame_size: f32, from: Pos2, to: Pos2, flow: WireFlow) -> [Pos2; 4] {
+  let [a, b, _, _, c, d] = wire_bezier_5(frame_size, from, to, flow);
   [a, b, c, d]
 }
 
@@ -204,9 +291,11 @@ pub fn draw_wire(
   downscale: bool,
   from: Pos2,
   to: Pos2,
+  obstacles: &[Rect],
   mut stroke: Stroke,
   threshold: f32,
   style: WireStyle,
+  flow: WireFlow,
 ) {
   if !ui.is_visible() {
     return;
@@ -219,7 +308,7 @@ pub fn draw_wire(
 
   let frame_size = adjust_frame_size(frame_size, upscale, downscale, from, to);
 
-  let args = WireArgs { frame_size, from, to, radius: 0.0 };
+  let args = WireArgs { frame_size, from, to, radius: 0.0, flow };
 
   match style {
     WireStyle::Line => {
@@ -240,6 +329,34 @@ pub fn draw_wire(
       let args = WireArgs { radius: corner_radius, ..args };
       draw_axis_aligned(ui, wire, args, stroke, threshold, shapes);
     }
+
+    WireStyle::Routed { corner_radius } => {
+      let args = WireArgs { radius: corner_radius, ..args };
+      draw_routed(ui, wire, args, obstacles, stroke, threshold, shapes);
+    }
+  }
+}
+
+/// Replaces wire shapes starting from `first` with dashed lines along the same paths.
+pub fn dash_wire_shapes(shapes: &mut Vec<Shape>, first: usize) {
+  for shape in shapes.split_off(first) {
+    let (points, stroke) = match shape {
+      Shape::LineSegment { points, stroke } => (points.to_vec(), stroke),
+      Shape::Path(path) => match path.stroke.color {
+        ColorMode::Solid(color) => (path.points, Stroke::new(path.stroke.width, color)),
+        ColorMode::UV(_) => {
+          shapes.push(Shape::Path(path));
+          continue;
+        }
+      },
+      shape => {
+        shapes.push(shape);
+        continue;
+      }
+    };
+
+    let dash = stroke.width * 4.0;
+    Shape::dashed_line_many(&points, stroke, dash, dash * 0.75, shapes);
   }
 }
 
@@ -252,13 +369,15 @@ pub fn hit_wire(
   downscale: bool,
   from: Pos2,
   to: Pos2,
+  obstacles: &[Rect],
   pos: Pos2,
   hit_threshold: f32,
   style: WireStyle,
+  flow: WireFlow,
 ) -> bool {
   let frame_size = adjust_frame_size(frame_size, upscale, downscale, from, to);
 
-  let args = WireArgs { frame_size, from, to, radius: 0.0 };
+  let args = WireArgs { frame_size, from, to, radius: 0.0, flow };
 
   match style {
     WireStyle::Line => {
@@ -282,6 +401,10 @@ pub fn hit_wire(
       let args = WireArgs { radius: corner_radius, ..args };
       hit_wire_axis_aligned(ctx, wire, args, pos, hit_threshold)
     }
+    WireStyle::Routed { corner_radius } => {
+      let args = WireArgs { radius: corner_radius, ..args };
+      hit_wire_routed(ctx, wire, args, obstacles, pos, hit_threshold)
+    }
   }
 }
 
@@ -430,11 +553,18 @@ struct WireArgs {
   from: Pos2,
   to: Pos2,
   radius: f32,
+  flow: WireFlow,
 }
 
 impl Default for WireArgs {
   fn default() -> Self {
-    WireArgs { frame_size: 0.0, from: Pos2::ZERO, to: Pos2::ZERO, radius: 0.0 }
+    WireArgs {
+      frame_size: 0.0,
+      from: Pos2::ZERO,
+      to: Pos2::ZERO,
+      radius: 0.0,
+      flow: WireFlow::Down,
+    }
   }
 }
 
@@ -596,12 +726,86 @@ impl WireCacheAA {
   }
 }
 
+struct WireCacheRouted {
+  generation: u32,
+  args: WireArgs,
+  /// Area where obstacles affect the route.
+  window: Rect,
+  /// Obstacles in the window when the route was found.
+  nearby: Vec<Rect>,
+  corners: Vec<Pos2>,
+  aabb: Rect,
+  threshold: f32,
+  line: Vec<Pos2>,
+}
+
+impl Default for WireCacheRouted {
+  fn default() -> Self {
+    WireCacheRouted {
+      generation: 0,
+      args: WireArgs::default(),
+      window: Rect::NOTHING,
+      nearby: Vec::new(),
+      corners: Vec::new(),
+      aabb: Rect::NOTHING,
+      threshold: 0.0,
+      line: Vec::new(),
+    }
+  }
+}
+
+impl WireCacheRouted {
+  fn line(&mut self, threshold: f32) -> Vec<Pos2> {
+    #[allow(clippy::float_cmp)]
+    if !self.line.is_empty() && self.threshold == threshold {
+      return self.line.clone();
+    }
+
+    let mut line = Vec::new();
+
+    for (idx, &corner) in self.corners.iter().enumerate() {
+      let (Some(&prev), Some(&next)) =
+        (idx.checked_sub(1).and_then(|prev| self.corners.get(prev)), self.corners.get(idx + 1))
+      else {
+        line.push(corner);
+        continue;
+      };
+
+      let radius =
+        self.args.radius.min(corner.distance(prev) / 2.0).min(corner.distance(next) / 2.0);
+      if radius <= 0.0 {
+        line.push(corner);
+        continue;
+      }
+
+      // Quarter circle between the two segments meeting at the corner.
+      let start = corner + (prev - corner).normalized() * radius;
+      let end = corner + (next - corner).normalized() * radius;
+      let center = start + (end - corner);
+      let samples = turn_samples_number(radius, threshold);
+
+      for j in 0..=samples {
+        #[allow(clippy::cast_precision_loss)]
+        let a = std::f32::consts::FRAC_PI_2 * (j as f32 / samples as f32);
+        let (sin_a, cos_a) = a.sin_cos();
+        line.push(center + (start - center) * cos_a + (end - center) * sin_a);
+      }
+    }
+
+    self.threshold = threshold;
+    self.line.clone_from(&line);
+
+    line
+  }
+}
+
 #[derive(Default)]
 struct WiresCache {
   generation: u32,
   bezier_3: HashMap<WireId, WireCache3>,
   bezier_5: HashMap<WireId, WireCache5>,
   axis_aligned: HashMap<WireId, WireCacheAA>,
+  routed: HashMap<WireId, WireCacheRouted>,
 }
 
 impl CacheTrait for WiresCache {
@@ -609,12 +813,13 @@ impl CacheTrait for WiresCache {
     self.bezier_3.retain(|_, cache| cache.generation == self.generation);
     self.bezier_5.retain(|_, cache| cache.generation == self.generation);
     self.axis_aligned.retain(|_, cache| cache.generation == self.generation);
+    self.routed.retain(|_, cache| cache.generation == self.generation);
 
     self.generation = self.generation.wrapping_add(1);
   }
 
   fn len(&self) -> usize {
-    self.bezier_3.len() + self.bezier_5.len() + self.axis_aligned.len()
+    self.bezier_3.len() + self.bezier_5.len() + self.axis_aligned.len() + self.routed.len()
   }
 
   fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
@@ -632,7 +837,7 @@ impl WiresCache {
       return cached;
     }
 
-    let points = wire_bezier_3(args.frame_size, args.from, args.to);
+    let points = wire_bezier_3(args.frame_size, args.from, args.to, args.flow);
     let aabb = Rect::from_points(&points);
 
     cached.args = args;
@@ -652,7 +857,7 @@ impl WiresCache {
       return cached;
     }
 
-    let points = wire_bezier_5(args.frame_size, args.from, args.to);
+    let points = wire_bezier_5(args.frame_size, args.from, args.to, args.flow);
     let aabb = Rect::from_points(&points);
 
     cached.args = args;
@@ -673,7 +878,7 @@ impl WiresCache {
       return cached;
     }
 
-    let aawire = wire_axis_aligned(args.radius, args.frame_size, args.from, args.to);
+    let aawire = wire_axis_aligned(args.radius, args.frame_size, args.from, args.to, args.flow);
 
     cached.args = args;
     cached.aawire = aawire;
@@ -681,6 +886,34 @@ impl WiresCache {
 
     cached
   }
+
+  pub fn get_routed(
+    &mut self,
+    wire: WireId,
+    args: WireArgs,
+    obstacles: &[Rect],
+  ) -> &mut WireCacheRouted {
+    let cached = self.routed.entry(wire).or_default();
+
+    cached.generation = self.generation;
+
+    if cached.args == args
+      && route::obstacles_in(cached.window, obstacles).eq(cached.nearby.iter().copied())
+    {
+      return cached;
+    }
+
+    let route = route::route(args.frame_size, args.from, args.to, args.flow, obstacles);
+
+    cached.args = args;
+    cached.window = route.window;
+    cached.nearby = route::obstacles_in(route.window, obstacles).collect();
+    cached.aabb = Rect::from_points(&route.points);
+    cached.corners = route.points;
+    cached.line.clear();
+
+    cached
+  }
 }
 
 #[inline(never)]
@@ -992,6 +1225,19 @@ struct AxisAlignedWire {
   turn_radii: [f32; 4],
 }
 
+impl AxisAlignedWire {
+  /// Maps all points of the wire.
+  fn map(self, f: impl Fn(Pos2) -> Pos2) -> Self {
+    AxisAlignedWire {
+      aabb: Rect::from_two_pos(f(self.aabb.min), f(self.aabb.max)),
+      turns: self.turns,
+      segments: self.segments.map(|(a, b)| (f(a), f(b))),
+      turn_centers: self.turn_centers.map(&f),
+      turn_radii: self.turn_radii,
+    }
+  }
+}
+
 impl Default for AxisAlignedWire {
   #[inline]
   fn default() -> Self {
@@ -1005,8 +1251,31 @@ impl Default for AxisAlignedWire {
   }
 }
 
+/// Returns axis aligned wire with turns along the direction of `flow`.
+fn wire_axis_aligned(
+  corner_radius: f32,
+  frame_size: f32,
+  from: Pos2,
+  to: Pos2,
+  flow: WireFlow,
+) -> AxisAlignedWire {
+  wire_axis_aligned_vertical(
+    corner_radius,
+    frame_size,
+    flow.enter_vertical(from),
+    flow.enter_vertical(to),
+  )
+  .map(|p| flow.restore_vertical(p))
+}
+
+/// Returns axis aligned wire flowing down.
 #[allow(clippy::too_many_lines)]
-fn wire_axis_aligned(corner_radius: f32, frame_size: f32, from: Pos2, to: Pos2) -> AxisAlignedWire {
+fn wire_axis_aligned_vertical(
+  corner_radius: f32,
+  frame_size: f32,
+  from: Pos2,
+  to: Pos2,
+) -> AxisAlignedWire {
   let corner_radius = corner_radius.max(0.0);
 
   let half_width = f32::abs(from.x - to.x) / 2.0;
@@ -1196,6 +1465,51 @@ fn draw_axis_aligned(
   });
 }
 
+fn draw_routed(
+  ui: &Ui,
+  wire: WireId,
+  args: WireArgs,
+  obstacles: &[Rect],
+  stroke: Stroke,
+  threshold: f32,
+  shapes: &mut Vec<Shape>,
+) {
+  debug_assert!(ui.is_visible(), "Must be checked earlier");
+
+  let clip_rect = ui.clip_rect();
+  ui.memory_mut(|m| {
+    let cached = m.caches.cache::<WiresCache>().get_routed(wire, args, obstacles);
+
+    if cached.aabb.intersects(clip_rect) {
+      shapes.push(Shape::line(cached.line(threshold), stroke));
+    }
+  });
+}
+
+fn hit_wire_routed(
+  ctx: &Context,
+  wire: WireId,
+  args: WireArgs,
+  obstacles: &[Rect],
+  pos: Pos2,
+  hit_threshold: f32,
+) -> bool {
+  let (aabb, corners) = ctx.memory_mut(|m| {
+    let cache = m.caches.cache::<WiresCache>().get_routed(wire, args, obstacles);
+
+    (cache.aabb, cache.corners.clone())
+  });
+
+  if !aabb.expand(hit_threshold).contains(pos) {
+    return false;
+  }
+
+  // Segments are axis aligned, rounded corners stay within their bounding boxes.
+  corners
+    .windows(2)
+    .any(|segment| Rect::from_two_pos(segment[0], segment[1]).expand(hit_threshold).contains(pos))
+}
+
 /// Very basic lower-bound algorithm
 /// Finds the smallest number in range [min, max) that satisfies the predicate
 /// If no such number exists, returns max
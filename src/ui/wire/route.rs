@@ -0,0 +1,369 @@
+//! Orthogonal routing of wires around nodes.
+//!
+//! Wires leave output pins and enter input pins straight along the flow, for the wire frame size.
+//! In between they follow the cheapest path on a sparse grid made of borders of nodes
+//! near the wire, where each turn costs as much as a wire frame of length.
+//! Nodes are kept half a wire frame away from the path where there is room for it.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use egui::{Pos2, Rect, Vec2, pos2, vec2};
+
+use super::WireFlow;
+
+/// Number of times the area searched for a route grows before all nodes are considered.
+const MAX_WINDOWS: usize = 4;
+
+/// Route found around obstacles.
+pub(super) struct Route {
+  /// Corners of the route, starting at the output pin and ending at the input pin.
+  pub points: Vec<Pos2>,
+
+  /// Area where obstacles were considered.
+  pub window: Rect,
+}
+
+/// Returns obstacles that a route searched in `window` depends on.
+pub(super) fn obstacles_in(window: Rect, obstacles: &[Rect]) -> impl Iterator<Item = Rect> + '_ {
+  obstacles.iter().copied().filter(move |obstacle| obstacle.intersects(window))
+}
+
+/// Finds orthogonal route from `from` to `to` around `obstacles`.
+///
+/// Search starts close to the ends of the wire and widens until a route is found.
+/// If obstacles leave no way, they are ignored.
+pub(super) fn route(
+  frame_size: f32,
+  from: Pos2,
+  to: Pos2,
+  flow: WireFlow,
+  obstacles: &[Rect],
+) -> Route {
+  let stub = frame_size.max(1.0);
+  let dir = flow_direction(flow);
+  let start = from + dir * stub_length(from, dir, stub, obstacles);
+  let end = to - dir * stub_length(to, -dir, stub, obstacles);
+
+  // Nodes closer to the ends than the padding are passed tightly.
+  let padded = obstacles
+    .iter()
+    .map(|obstacle| {
+      let padded = obstacle.expand(stub / 2.0);
+      if inside(&padded, start) || inside(&padded, end) { *obstacle } else { padded }
+    })
+    .collect::<Vec<_>>();
+
+  // Overlapping nodes leave no room at the ends, the wire goes through them.
+  let start = escape(start, dir, &padded);
+  let end = escape(end, -dir, &padded);
+
+  let ends = Rect::from_two_pos(start, end).union(Rect::from_two_pos(from, to));
+  let all = padded.iter().fold(ends, |all, obstacle| all.union(*obstacle));
+
+  let mut reach = stub * 2.0;
+  for _ in 0..MAX_WINDOWS {
+    let window = ends.expand(reach);
+    if window.contains_rect(all) {
+      break;
+    }
+
+    let nearby = padded.iter().copied().filter(|obstacle| obstacle.intersects(window));
+    if let Some(path) = search(start, end, dir, stub, window, &nearby.collect::<Vec<_>>()) {
+      return Route { points: corners(from, path, to), window: window.expand(stub / 2.0) };
+    }
+    reach *= 4.0;
+  }
+
+  // All nodes were considered, only those inside `all` can change the route.
+  let window = all.expand(stub);
+  if let Some(path) = search(start, end, dir, stub, window, &padded) {
+    return Route { points: corners(from, path, to), window };
+  }
+
+  // No way around, go straight through.
+  let path =
+    search(start, end, dir, stub, ends.expand(stub), &[]).unwrap_or_else(|| vec![start, end]);
+  Route { points: corners(from, path, to), window }
+}
+
+/// Returns unit vector of the direction wires flow in.
+const fn flow_direction(flow: WireFlow) -> Vec2 {
+  match flow {
+    WireFlow::Down => vec2(0.0, 1.0),
+    WireFlow::Up => vec2(0.0, -1.0),
+    WireFlow::Right => vec2(1.0, 0.0),
+    WireFlow::Left => vec2(-1.0, 0.0),
+  }
+}
+
+/// Returns true if `p` is strictly inside the rect, borders are free to pass along.
+fn inside(rect: &Rect, p: Pos2) -> bool {
+  rect.min.x < p.x && p.x < rect.max.x && rect.min.y < p.y && p.y < rect.max.y
+}
+
+/// Returns length of the straight wire end leaving `pin` along `dir`.
+///
+/// It is `stub` long, unless there is a node closer than that ahead,
+/// then it stops halfway to the node.
+fn stub_length(pin: Pos2, dir: Vec2, stub: f32, obstacles: &[Rect]) -> f32 {
+  obstacles
+    .iter()
+    .filter_map(|obstacle| {
+      let (along, across, near, low, high) = if dir.x == 0.0 {
+        let near = if dir.y > 0.0 { obstacle.min.y } else { -obstacle.max.y };
+        (pin.y * dir.y, pin.x, near, obstacle.min.x, obstacle.max.x)
+      } else {
+        let near = if dir.x > 0.0 { obstacle.min.x } else { -obstacle.max.x };
+        (pin.x * dir.x, pin.y, near, obstacle.min.y, obstacle.max.y)
+      };
+      (low < across && across < high && near > along).then_some((near - along) / 2.0)
+    })
+    .fold(stub, f32::min)
+}
+
+/// Moves `p` along `dir` until it is out of all obstacles.
+fn escape(mut p: Pos2, dir: Vec2, obstacles: &[Rect]) -> Pos2 {
+  for _ in 0..=obstacles.len() {
+    let Some(obstacle) = obstacles.iter().find(|obstacle| inside(obstacle, p)) else {
+      break;
+    };
+    p = match dir {
+      Vec2 { x, .. } if x > 0.0 => pos2(obstacle.max.x, p.y),
+      Vec2 { x, .. } if x < 0.0 => pos2(obstacle.min.x, p.y),
+      Vec2 { y, .. } if y > 0.0 => pos2(p.x, obstacle.max.y),
+      _ => pos2(p.x, obstacle.min.y),
+    };
+  }
+  p
+}
+
+/// Joins ends of the wire with the path between them, leaving out points that are not corners.
+fn corners(from: Pos2, path: Vec<Pos2>, to: Pos2) -> Vec<Pos2> {
+  let mut points: Vec<Pos2> = Vec::with_capacity(path.len() + 2);
+  for p in std::iter::once(from).chain(path).chain(std::iter::once(to)) {
+    if points.last() == Some(&p) {
+      continue;
+    }
+    if let [.., a, b] = points[..] {
+      // Points come from the same grid, so equal coordinates are exactly equal.
+      #[allow(clippy::float_cmp)]
+      let collinear = (a.x == b.x && b.x == p.x) || (a.y == b.y && b.y == p.y);
+      if collinear {
+        points.pop();
+      }
+    }
+    points.push(p);
+  }
+  points
+}
+
+/// State of the search, ordered by lowest estimated cost first.
+#[derive(Clone, Copy, PartialEq)]
+struct Step {
+  estimate: f32,
+  cost: f32,
+  node: usize,
+  dir: usize,
+}
+
+impl Eq for Step {}
+
+impl Ord for Step {
+  fn cmp(&self, other: &Self) -> Ordering {
+    other.estimate.total_cmp(&self.estimate).then_with(|| other.node.cmp(&self.node))
+  }
+}
+
+impl PartialOrd for Step {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+/// Steps between neighboring points of the grid: right, down, left, up.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// Returns index of the direction in [`DIRECTIONS`].
+fn direction_index(dir: Vec2) -> usize {
+  match dir {
+    Vec2 { x, .. } if x > 0.0 => 0,
+    Vec2 { y, .. } if y > 0.0 => 1,
+    Vec2 { x, .. } if x < 0.0 => 2,
+    _ => 3,
+  }
+}
+
+/// Points and segments of the search grid that lie inside obstacles.
+struct Blocked {
+  /// Points, by node index.
+  points: Vec<bool>,
+  /// Segments from each point to its right neighbor, by index of the left point.
+  horizontal: Vec<bool>,
+  /// Segments from each point to its lower neighbor, by index of the upper point.
+  vertical: Vec<bool>,
+}
+
+impl Blocked {
+  /// Marks grid points and segments that are strictly inside `obstacles`.
+  fn new(xs: &[f32], ys: &[f32], obstacles: &[Rect]) -> Self {
+    let midpoints = |values: &[f32]| {
+      values.windows(2).map(|pair| f32::midpoint(pair[0], pair[1])).collect::<Vec<_>>()
+    };
+    // Segments are blocked where their middle is.
+    let (column_gaps, row_gaps) = (midpoints(xs), midpoints(ys));
+
+    let size = xs.len() * ys.len();
+    let mut blocked = Blocked {
+      points: vec![false; size],
+      horizontal: vec![false; size],
+      vertical: vec![false; size],
+    };
+
+    for obstacle in obstacles {
+      let (columns, rows) =
+        (between(xs, obstacle.min.x, obstacle.max.x), between(ys, obstacle.min.y, obstacle.max.y));
+      let segments_across = between(&column_gaps, obstacle.min.x, obstacle.max.x);
+      let segments_along = between(&row_gaps, obstacle.min.y, obstacle.max.y);
+
+      for y in rows.clone() {
+        for x in columns.clone() {
+          blocked.points[y * xs.len() + x] = true;
+        }
+        for x in segments_across.clone() {
+          blocked.horizontal[y * xs.len() + x] = true;
+        }
+      }
+      for y in segments_along {
+        for x in columns.clone() {
+          blocked.vertical[y * xs.len() + x] = true;
+        }
+      }
+    }
+
+    blocked
+  }
+
+  /// Returns true if the segment between neighboring nodes `from` and `to` is blocked.
+  fn segment(&self, from: usize, to: usize, width: usize) -> bool {
+    let first = from.min(to);
+    if from.abs_diff(to) == width { self.vertical[first] } else { self.horizontal[first] }
+  }
+}
+
+/// Returns range of sorted `values` strictly between `low` and `high`.
+fn between(values: &[f32], low: f32, high: f32) -> std::ops::Range<usize> {
+  let start = values.partition_point(|&value| value <= low);
+  let end = values.partition_point(|&value| value < high);
+  start..end.max(start)
+}
+
+/// Finds cheapest path from `start` to `end` with A* on the grid of obstacle borders,
+/// starting and ending in direction `dir`.
+fn search(
+  start: Pos2,
+  end: Pos2,
+  dir: Vec2,
+  bend_cost: f32,
+  window: Rect,
+  obstacles: &[Rect],
+) -> Option<Vec<Pos2>> {
+  let coordinates = |pick: fn(Pos2) -> f32| {
+    let mut values = vec![pick(start), pick(end), pick(window.min), pick(window.max)];
+    for obstacle in obstacles {
+      values.push(pick(obstacle.min));
+      values.push(pick(obstacle.max));
+    }
+    let (low, high) = (pick(window.min), pick(window.max));
+    values.retain(|value| (low..=high).contains(value));
+    values.sort_by(f32::total_cmp);
+    values.dedup();
+    values
+  };
+  let xs = coordinates(|p| p.x);
+  let ys = coordinates(|p| p.y);
+
+  let point = |node: usize| pos2(xs[node % xs.len()], ys[node / xs.len()]);
+  let find = |p: Pos2| {
+    let x = xs.binary_search_by(|x| x.total_cmp(&p.x)).ok()?;
+    let y = ys.binary_search_by(|y| y.total_cmp(&p.y)).ok()?;
+    Some(y * xs.len() + x)
+  };
+  let (start_node, end_node) = (find(start)?, find(end)?);
+  let blocked = Blocked::new(&xs, &ys, obstacles);
+
+  let heuristic = |p: Pos2| (p.x - end.x).abs() + (p.y - end.y).abs();
+  let start_dir = direction_index(dir);
+
+  let states = xs.len() * ys.len() * DIRECTIONS.len();
+  let mut costs = vec![f32::INFINITY; states];
+  let mut previous = vec![usize::MAX; states];
+  let mut queue = BinaryHeap::new();
+
+  costs[start_node * 4 + start_dir] = 0.0;
+  queue.push(Step { estimate: heuristic(start), cost: 0.0, node: start_node, dir: start_dir });
+
+  while let Some(Step { cost, node, dir, .. }) = queue.pop() {
+    let state = node * 4 + dir;
+    if cost > costs[state] {
+      continue;
+    }
+
+    if node == end_node {
+      return Some(path(state, &previous, point));
+    }
+
+    let (x, y) = (node % xs.len(), node / xs.len());
+    let from = point(node);
+
+    for (next_dir, &(dx, dy)) in DIRECTIONS.iter().enumerate() {
+      if next_dir == (dir + 2) % 4 {
+        continue;
+      }
+      let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy)) else {
+        continue;
+      };
+      if nx >= xs.len() || ny >= ys.len() {
+        continue;
+      }
+
+      let next = ny * xs.len() + nx;
+      let to = point(next);
+      if (next != end_node && blocked.points[next]) || blocked.segment(node, next, xs.len()) {
+        continue;
+      }
+
+      let mut bends = usize::from(next_dir != dir);
+      if next == end_node {
+        // Turning into the input pin costs one more bend.
+        bends += usize::from(next_dir != start_dir);
+      }
+      #[allow(clippy::cast_precision_loss)]
+      let next_cost = cost + (to - from).length() + bends as f32 * bend_cost;
+      let next_state = next * 4 + next_dir;
+      if next_cost < costs[next_state] {
+        costs[next_state] = next_cost;
+        previous[next_state] = state;
+        queue.push(Step {
+          estimate: next_cost + heuristic(to),
+          cost: next_cost,
+          node: next,
+          dir: next_dir,
+        });
+      }
+    }
+  }
+
+  None
+}
+
+/// Follows `previous` states back from `state` to the start.
+fn path(mut state: usize, previous: &[usize], point: impl Fn(usize) -> Pos2) -> Vec<Pos2> {
+  let mut points = vec![point(state / 4)];
+  while previous[state] != usize::MAX {
+    state = previous[state];
+    points.push(point(state / 4));
+  }
+  points.reverse();
+  points
+}